@@ -0,0 +1,66 @@
+// GitHub 风格的提示块：> [!NOTE]、> [!TIP]、> [!IMPORTANT]、> [!WARNING]、> [!CAUTION]
+use crate::theme::Theme;
+use crate::{Language, PLAIN_BOLD, RESET};
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum AlertKind {
+    Note,
+    Tip,
+    Important,
+    Warning,
+    Caution,
+}
+
+impl AlertKind {
+    // 解析引用第一行的 [!KIND] 标记（不区分大小写，标记需要单独成行）
+    pub fn parse(trimmed: &str) -> Option<Self> {
+        let kind = trimmed.strip_prefix("[!")?.strip_suffix(']')?;
+        match kind.to_ascii_uppercase().as_str() {
+            "NOTE" => Some(AlertKind::Note),
+            "TIP" => Some(AlertKind::Tip),
+            "IMPORTANT" => Some(AlertKind::Important),
+            "WARNING" => Some(AlertKind::Warning),
+            "CAUTION" => Some(AlertKind::Caution),
+            _ => None,
+        }
+    }
+
+    pub fn color(self, theme: &Theme) -> &str {
+        match self {
+            AlertKind::Note => &theme.alert_note,
+            AlertKind::Tip => &theme.alert_tip,
+            AlertKind::Important => &theme.alert_important,
+            AlertKind::Warning => &theme.alert_warning,
+            AlertKind::Caution => &theme.alert_caution,
+        }
+    }
+
+    fn icon(self) -> &'static str {
+        match self {
+            AlertKind::Note => "ℹ",
+            AlertKind::Tip => "✦",
+            AlertKind::Important => "✱",
+            AlertKind::Warning => "▲",
+            AlertKind::Caution => "⛒",
+        }
+    }
+
+    fn title(self, lang: &Language) -> &'static str {
+        match self {
+            AlertKind::Note => lang.alert_note,
+            AlertKind::Tip => lang.alert_tip,
+            AlertKind::Important => lang.alert_important,
+            AlertKind::Warning => lang.alert_warning,
+            AlertKind::Caution => lang.alert_caution,
+        }
+    }
+
+    // 标题行：图标加类型名，不能显示 Unicode 符号时省略图标
+    pub fn title_line(self, lang: &Language, theme: &Theme, unicode_glyphs: bool) -> String {
+        if unicode_glyphs {
+            format!("{}{}{} {}{}\n", PLAIN_BOLD, self.color(theme), self.icon(), self.title(lang), RESET)
+        } else {
+            format!("{}{}{}{}\n", PLAIN_BOLD, self.color(theme), self.title(lang), RESET)
+        }
+    }
+}
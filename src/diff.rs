@@ -0,0 +1,99 @@
+// diff / patch 代码块：删除行红底、新增行绿底，文件头和 hunk 头弱化显示
+// 能从文件头的扩展名识别出语言时，对每行内容再做语法高亮
+use crate::highlight::Highlighter;
+use crate::theme::SyntaxTheme;
+use crate::{GRAY, PLAIN_BOLD, RESET};
+
+const REMOVED_BG: &str = "\x1b[48;5;52m"; // 暗红色背景
+const ADDED_BG: &str = "\x1b[48;5;22m";   // 暗绿色背景
+pub const CLEAR_TO_EOL: &str = "\x1b[K";  // 用当前背景色填充到行尾
+
+#[derive(Clone)]
+pub struct DiffHighlighter {
+    in_hunk: bool,
+    // 删除行和新增行分别属于修改前后的文件，各自保存跨行的词法状态
+    old: Option<Highlighter>,
+    new: Option<Highlighter>,
+    colors: SyntaxTheme,
+}
+
+impl DiffHighlighter {
+    pub fn new(colors: &SyntaxTheme) -> Self {
+        Self {
+            in_hunk: false,
+            old: None,
+            new: None,
+            colors: colors.clone(),
+        }
+    }
+
+    pub fn highlight_line(&mut self, line: &str) -> String {
+        let body = line.trim_end_matches(['\r', '\n']);
+        let ending = &line[body.len()..];
+
+        let styled = if self.is_file_header(body) {
+            self.in_hunk = false;
+            // 每个文件按自己的扩展名重新选择高亮器
+            if let Some(path) = file_path(body) {
+                let highlighter = path.rsplit_once('.').and_then(|(_, ext)| Highlighter::new(ext, &self.colors));
+                self.old = highlighter.clone();
+                self.new = highlighter;
+            }
+            format!("{}{}{}{}", PLAIN_BOLD, GRAY, body, RESET)
+        } else if let Some(hunk) = body.strip_prefix("@@") {
+            // @@ -1,2 +1,3 @@ 后面可能跟着所在函数，函数部分正常显示
+            self.in_hunk = true;
+            let end = hunk.find("@@").map_or(body.len(), |i| i + 4);
+            format!("{}{}{}{}", GRAY, &body[..end], RESET, &body[end..])
+        } else if let Some(code) = body.strip_prefix('+') {
+            format!("{}+{}", ADDED_BG, with_background(highlight(&mut self.new, code), ADDED_BG))
+        } else if let Some(code) = body.strip_prefix('-') {
+            format!("{}-{}", REMOVED_BG, with_background(highlight(&mut self.old, code), REMOVED_BG))
+        } else if body.starts_with('\\') {
+            // \ No newline at end of file
+            format!("{}{}{}", GRAY, body, RESET)
+        } else {
+            // 上下文行同时属于修改前后的文件
+            let code = body.strip_prefix(' ').unwrap_or(body);
+            highlight(&mut self.old, code);
+            format!("{}{}", &body[..body.len() - code.len()], highlight(&mut self.new, code))
+        };
+        styled + ending
+    }
+
+    // diff --git、index、--- a/...、+++ b/... 等文件头
+    // hunk 中以 --- 开头的可能是删除的内容，只认带 a/、b/ 前缀或 /dev/null 的
+    fn is_file_header(&self, body: &str) -> bool {
+        let explicit = ["--- a/", "+++ b/", "--- /dev/null", "+++ /dev/null", "diff --git "];
+        if explicit.iter().any(|prefix| body.starts_with(prefix)) {
+            return true;
+        }
+        let headers = ["--- ", "+++ ", "diff ", "index ", "new file mode", "deleted file mode", "similarity index", "rename from", "rename to"];
+        !self.in_hunk && headers.iter().any(|prefix| body.starts_with(prefix))
+    }
+}
+
+// 从 --- a/src/main.rs、+++ b/src/main.rs 或 diff --git a/x b/x 中取出文件路径
+fn file_path(body: &str) -> Option<&str> {
+    let path = if let Some(rest) = body.strip_prefix("diff --git ") {
+        rest.split_whitespace().last()?
+    } else {
+        let rest = body.strip_prefix("--- ").or_else(|| body.strip_prefix("+++ "))?;
+        // 去掉 diff -u 输出中路径后面的时间戳
+        rest.split('\t').next()?.trim()
+    };
+    (path != "/dev/null").then_some(path)
+}
+
+fn highlight(highlighter: &mut Option<Highlighter>, code: &str) -> String {
+    match highlighter {
+        Some(highlighter) => highlighter.highlight_line(code),
+        None => code.to_string(),
+    }
+}
+
+// 高亮片段结束时的 RESET 会清除背景色，之后重新应用；背景填充到行尾
+fn with_background(highlighted: String, background: &str) -> String {
+    let restored = highlighted.replace(RESET, &format!("{}{}", RESET, background));
+    format!("{}{}{}", restored, CLEAR_TO_EOL, RESET)
+}
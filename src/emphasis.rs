@@ -0,0 +1,286 @@
+// 强调（斜体/粗体）解析：按 CommonMark 的分隔符串（delimiter run）左右侧规则
+// 判断 * 和 _ 能否开启/关闭强调，再用分隔符栈配对（含“3 的倍数”规则）
+// ~~删除线~~ 和 ==高亮== 也作为分隔符串参与同一个栈，因此可以与粗体、斜体互相嵌套
+// 与规范不同的一处：两个数字之间的 * 既不能开启也不能关闭强调，2*3*4 这样的算式按原文显示
+// （规范中 5*6*78 会渲染为 5<em>6</em>78，这在技术回答中几乎总是误判）
+use crate::inline::{Inline, Style};
+
+// 行内片段
+pub enum InlineToken {
+    Text(String),
+    Node(Inline), // 已经解析好的节点（行内代码、链接等）
+    Delimiter(DelimiterRun),
+}
+
+pub struct DelimiterRun {
+    ch: char,
+    count: usize,
+    can_open: bool,
+    can_close: bool,
+    // 配对结果：作为关闭符使用的长度（从左侧取），作为开启符使用的长度（从右侧取）
+    closes: Vec<usize>,
+    opens: Vec<usize>,
+}
+
+// CommonMark 意义上的标点：ASCII 标点，或非字母数字、非空白的其他字符
+fn is_punctuation(c: char) -> bool {
+    c.is_ascii_punctuation() || !c.is_ascii() && !c.is_alphanumeric() && !c.is_whitespace()
+}
+
+// 扫描 text[start..] 开头的分隔符串，返回分隔符串和它的字节长度
+// 行首/行尾视为空白
+pub fn scan_delimiter_run(text: &str, start: usize) -> (DelimiterRun, usize) {
+    let ch = text[start..].chars().next().unwrap();
+    let count = text[start..].chars().take_while(|&c| c == ch).count();
+    let end = start + count; // * _ ~ = 都是单字节
+
+    let before = text[..start].chars().next_back();
+    let after = text[end..].chars().next();
+    let before_space = before.is_none_or(char::is_whitespace);
+    let after_space = after.is_none_or(char::is_whitespace);
+    let before_punct = before.is_some_and(is_punctuation);
+    let after_punct = after.is_some_and(is_punctuation);
+
+    let left_flanking = !after_space && (!after_punct || before_space || before_punct);
+    let right_flanking = !before_space && (!before_punct || after_space || after_punct);
+
+    // _ 和 == 不能用于单词内部（a==b 是比较而不是高亮）；* 在两个数字之间时视为乘号
+    let between_digits = before.is_some_and(|c| c.is_ascii_digit()) && after.is_some_and(|c| c.is_ascii_digit());
+    let (can_open, can_close) = match ch {
+        '*' if between_digits => (false, false),
+        // 删除线和高亮只使用恰好两个字符的分隔符串
+        '~' | '=' if count != 2 => (false, false),
+        '*' | '~' => (left_flanking, right_flanking),
+        _ => (
+            left_flanking && (!right_flanking || before_punct),
+            right_flanking && (!left_flanking || after_punct),
+        ),
+    };
+
+    let run = DelimiterRun {
+        ch,
+        count,
+        can_open,
+        can_close,
+        closes: Vec::new(),
+        opens: Vec::new(),
+    };
+    (run, count)
+}
+
+// 配对分隔符，构建语法树
+pub fn build(mut tokens: Vec<InlineToken>) -> Vec<Inline> {
+    process_emphasis(&mut tokens);
+
+    // 每层未闭合的强调一个帧：(样式, 子节点)，最底层是根
+    let mut frames: Vec<(Option<Style>, Vec<Inline>)> = vec![(None, Vec::new())];
+    for token in tokens {
+        match token {
+            InlineToken::Text(text) => frames.last_mut().unwrap().1.push(Inline::Text(text)),
+            InlineToken::Node(node) => frames.last_mut().unwrap().1.push(node),
+            InlineToken::Delimiter(run) => {
+                for _ in &run.closes {
+                    let (style, children) = frames.pop().unwrap();
+                    frames.last_mut().unwrap().1.push(Inline::Styled(style.unwrap(), children));
+                }
+                let used: usize = run.closes.iter().chain(&run.opens).sum();
+                if run.count > used {
+                    let literal = run.ch.to_string().repeat(run.count - used);
+                    frames.last_mut().unwrap().1.push(Inline::Text(literal));
+                }
+                // 后配对的开启符在外层，先打开
+                for &len in run.opens.iter().rev() {
+                    let style = match (run.ch, len) {
+                        ('~', _) => Style::Strike,
+                        ('=', _) => Style::Highlight,
+                        (_, 2) => Style::Strong,
+                        _ => Style::Emphasis,
+                    };
+                    frames.push((Some(style), Vec::new()));
+                }
+            }
+        }
+    }
+    frames.pop().unwrap().1
+}
+
+// CommonMark 附录中的 process emphasis 算法
+fn process_emphasis(tokens: &mut [InlineToken]) {
+    // 分隔符栈：(token 下标, 剩余长度)
+    let mut stack: Vec<(usize, usize)> = tokens
+        .iter()
+        .enumerate()
+        .filter_map(|(i, t)| match t {
+            InlineToken::Delimiter(run) => Some((i, run.count)),
+            _ => None,
+        })
+        .collect();
+
+    let mut current = 0;
+    while current < stack.len() {
+        let (closer_index, closer_remaining) = stack[current];
+        let closer = delimiter(tokens, closer_index);
+        if !closer.can_close {
+            current += 1;
+            continue;
+        }
+        let (closer_ch, closer_count, closer_can_open) = (closer.ch, closer.count, closer.can_open);
+
+        // 向前查找可以配对的开启符
+        let opener = (0..current).rev().find(|&i| {
+            let opener = delimiter(tokens, stack[i].0);
+            let same_kind = opener.ch == closer_ch && opener.can_open;
+            // 规则 3：两侧之一可同时开启和关闭时，长度之和不能是 3 的倍数（除非两者都是 3 的倍数）
+            // 只适用于 * 和 _，~~ 和 == 的长度固定为 2
+            let rule_of_three = matches!(closer_ch, '*' | '_')
+                && (opener.can_close || closer_can_open)
+                && (opener.count + closer_count).is_multiple_of(3)
+                && !(opener.count.is_multiple_of(3) && closer_count.is_multiple_of(3));
+            same_kind && !rule_of_three
+        });
+
+        let Some(opener_pos) = opener else {
+            // 找不到开启符：只能关闭的分隔符从栈中移除
+            if !closer_can_open {
+                stack.remove(current);
+            } else {
+                current += 1;
+            }
+            continue;
+        };
+
+        let (opener_index, opener_remaining) = stack[opener_pos];
+        let len = if opener_remaining >= 2 && closer_remaining >= 2 { 2 } else { 1 };
+        delimiter_mut(tokens, opener_index).opens.push(len);
+        delimiter_mut(tokens, closer_index).closes.push(len);
+
+        // 开启符和关闭符之间的分隔符不能再参与配对
+        stack.drain(opener_pos + 1..current);
+        current = opener_pos + 1;
+        stack[opener_pos].1 -= len;
+        stack[current].1 -= len;
+        if stack[current].1 == 0 {
+            stack.remove(current);
+        }
+        if stack[opener_pos].1 == 0 {
+            stack.remove(opener_pos);
+            current -= 1;
+        }
+    }
+}
+
+fn delimiter(tokens: &[InlineToken], index: usize) -> &DelimiterRun {
+    match &tokens[index] {
+        InlineToken::Delimiter(run) => run,
+        _ => unreachable!(),
+    }
+}
+
+fn delimiter_mut(tokens: &mut [InlineToken], index: usize) -> &mut DelimiterRun {
+    match &mut tokens[index] {
+        InlineToken::Delimiter(run) => run,
+        _ => unreachable!(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // 把文字拆成普通文字和 * _ ~ = 分隔符串，配对后输出为 HTML，便于与规范中的例子对比
+    fn render(text: &str) -> String {
+        let mut tokens = Vec::new();
+        let mut plain = String::new();
+        let mut pos = 0;
+        while let Some(c) = text[pos..].chars().next() {
+            if matches!(c, '*' | '_' | '~' | '=') {
+                if !plain.is_empty() {
+                    tokens.push(InlineToken::Text(std::mem::take(&mut plain)));
+                }
+                let (run, len) = scan_delimiter_run(text, pos);
+                tokens.push(InlineToken::Delimiter(run));
+                pos += len;
+            } else {
+                plain.push(c);
+                pos += c.len_utf8();
+            }
+        }
+        if !plain.is_empty() {
+            tokens.push(InlineToken::Text(plain));
+        }
+        html(&build(tokens))
+    }
+
+    fn html(nodes: &[Inline]) -> String {
+        nodes
+            .iter()
+            .map(|node| match node {
+                Inline::Text(text) => text.clone(),
+                Inline::Styled(Style::Emphasis, children) => format!("<em>{}</em>", html(children)),
+                Inline::Styled(Style::Strong, children) => format!("<strong>{}</strong>", html(children)),
+                Inline::Styled(Style::Strike, children) => format!("<del>{}</del>", html(children)),
+                Inline::Styled(Style::Highlight, children) => format!("<mark>{}</mark>", html(children)),
+                _ => unreachable!(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn flanking() {
+        assert_eq!(render("*foo bar*"), "<em>foo bar</em>");
+        assert_eq!(render("a * foo bar*"), "a * foo bar*");
+        assert_eq!(render("* a *"), "* a *");
+        assert_eq!(render("foo*bar*"), "foo<em>bar</em>");
+        assert_eq!(render("*foo bar *"), "*foo bar *");
+        assert_eq!(render("**foo bar **"), "**foo bar **");
+        assert_eq!(render("*(*foo*)*"), "<em>(<em>foo</em>)</em>");
+        assert_eq!(render("foo-_(bar)_"), "foo-<em>(bar)</em>");
+        assert_eq!(render("*foo _bar* baz_"), "<em>foo _bar</em> baz_");
+    }
+
+    // 有意偏离规范：数字之间的 * 是乘号（规范中 5*6*78 为 5<em>6</em>78）
+    #[test]
+    fn star_between_digits() {
+        assert_eq!(render("2*3*4"), "2*3*4");
+        assert_eq!(render("5*6*78"), "5*6*78");
+        assert_eq!(render("2**3**4"), "2**3**4");
+        assert_eq!(render("2 * 3 * 4"), "2 * 3 * 4");
+        // 只有一侧是数字时仍按规范处理
+        assert_eq!(render("*2*"), "<em>2</em>");
+        assert_eq!(render("x*2*y"), "x<em>2</em>y");
+    }
+
+    #[test]
+    fn intraword_underscore() {
+        assert_eq!(render("_foo bar_"), "<em>foo bar</em>");
+        assert_eq!(render("foo_bar_"), "foo_bar_");
+        assert_eq!(render("5_6_78"), "5_6_78");
+        assert_eq!(render("snake_case_name"), "snake_case_name");
+        assert_eq!(render("_foo_bar_baz_"), "<em>foo_bar_baz</em>");
+        assert_eq!(render("__foo, __bar__, baz__"), "<strong>foo, <strong>bar</strong>, baz</strong>");
+    }
+
+    #[test]
+    fn strike_and_highlight() {
+        assert_eq!(render("~~foo~~"), "<del>foo</del>");
+        assert_eq!(render("==foo bar=="), "<mark>foo bar</mark>");
+        assert_eq!(render("x == 1 or y == 2"), "x == 1 or y == 2");
+        assert_eq!(render("if a==b && c==d"), "if a==b && c==d");
+        assert_eq!(render("~~ not ~~"), "~~ not ~~");
+        assert_eq!(render("~~~foo~~~"), "~~~foo~~~");
+        assert_eq!(render("~~**bold** text~~"), "<del><strong>bold</strong> text</del>");
+        assert_eq!(render("**==both==**"), "<strong><mark>both</mark></strong>");
+        assert_eq!(render("*a ~~b* c~~"), "<em>a ~~b</em> c~~");
+    }
+
+    #[test]
+    fn rule_of_three() {
+        assert_eq!(render("*foo**bar*"), "<em>foo**bar</em>");
+        assert_eq!(render("*foo**bar**baz*"), "<em>foo<strong>bar</strong>baz</em>");
+        assert_eq!(render("***foo** bar*"), "<em><strong>foo</strong> bar</em>");
+        assert_eq!(render("foo***bar***baz"), "foo<em><strong>bar</strong></em>baz");
+        assert_eq!(render("foo******bar*********baz"), "foo<strong><strong><strong>bar</strong></strong></strong>***baz");
+        assert_eq!(render("**foo*"), "*<em>foo</em>");
+        assert_eq!(render("*foo**"), "<em>foo</em>*");
+    }
+}
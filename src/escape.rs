@@ -0,0 +1,234 @@
+// 反斜杠转义与 HTML 实体解码
+// 反斜杠后跟 ASCII 标点时输出标点本身；&name; / &#123; / &#x1F600; 解码为对应字符
+
+// 常用的命名实体；不在表中的实体原样输出
+const NAMED_ENTITIES: &[(&str, &str)] = &[
+    ("amp", "&"),
+    ("lt", "<"),
+    ("gt", ">"),
+    ("quot", "\""),
+    ("apos", "'"),
+    ("nbsp", "\u{a0}"),
+    ("ensp", "\u{2002}"),
+    ("emsp", "\u{2003}"),
+    ("thinsp", "\u{2009}"),
+    ("zwnj", "\u{200c}"),
+    ("zwj", "\u{200d}"),
+    ("shy", "\u{ad}"),
+    ("copy", "©"),
+    ("reg", "®"),
+    ("trade", "™"),
+    ("hellip", "…"),
+    ("mdash", "—"),
+    ("ndash", "–"),
+    ("lsquo", "‘"),
+    ("rsquo", "’"),
+    ("sbquo", "‚"),
+    ("ldquo", "“"),
+    ("rdquo", "”"),
+    ("bdquo", "„"),
+    ("laquo", "«"),
+    ("raquo", "»"),
+    ("lsaquo", "‹"),
+    ("rsaquo", "›"),
+    ("bull", "•"),
+    ("middot", "·"),
+    ("dagger", "†"),
+    ("Dagger", "‡"),
+    ("permil", "‰"),
+    ("prime", "′"),
+    ("Prime", "″"),
+    ("sect", "§"),
+    ("para", "¶"),
+    ("deg", "°"),
+    ("plusmn", "±"),
+    ("times", "×"),
+    ("divide", "÷"),
+    ("minus", "−"),
+    ("micro", "µ"),
+    ("frac12", "½"),
+    ("frac14", "¼"),
+    ("frac34", "¾"),
+    ("sup1", "¹"),
+    ("sup2", "²"),
+    ("sup3", "³"),
+    ("cent", "¢"),
+    ("pound", "£"),
+    ("yen", "¥"),
+    ("euro", "€"),
+    ("curren", "¤"),
+    ("iexcl", "¡"),
+    ("iquest", "¿"),
+    ("larr", "←"),
+    ("uarr", "↑"),
+    ("rarr", "→"),
+    ("darr", "↓"),
+    ("harr", "↔"),
+    ("lArr", "⇐"),
+    ("uArr", "⇑"),
+    ("rArr", "⇒"),
+    ("dArr", "⇓"),
+    ("hArr", "⇔"),
+    ("le", "≤"),
+    ("ge", "≥"),
+    ("ne", "≠"),
+    ("asymp", "≈"),
+    ("equiv", "≡"),
+    ("infin", "∞"),
+    ("sum", "∑"),
+    ("prod", "∏"),
+    ("radic", "√"),
+    ("part", "∂"),
+    ("nabla", "∇"),
+    ("isin", "∈"),
+    ("notin", "∉"),
+    ("forall", "∀"),
+    ("exist", "∃"),
+    ("empty", "∅"),
+    ("and", "∧"),
+    ("or", "∨"),
+    ("cap", "∩"),
+    ("cup", "∪"),
+    ("sub", "⊂"),
+    ("sup", "⊃"),
+    ("int", "∫"),
+    ("there4", "∴"),
+    ("check", "✓"),
+    ("cross", "✗"),
+    ("star", "☆"),
+    ("hearts", "♥"),
+    ("spades", "♠"),
+    ("clubs", "♣"),
+    ("diams", "♦"),
+    ("Alpha", "Α"),
+    ("Beta", "Β"),
+    ("Gamma", "Γ"),
+    ("Delta", "Δ"),
+    ("Theta", "Θ"),
+    ("Lambda", "Λ"),
+    ("Pi", "Π"),
+    ("Sigma", "Σ"),
+    ("Phi", "Φ"),
+    ("Psi", "Ψ"),
+    ("Omega", "Ω"),
+    ("alpha", "α"),
+    ("beta", "β"),
+    ("gamma", "γ"),
+    ("delta", "δ"),
+    ("epsilon", "ε"),
+    ("zeta", "ζ"),
+    ("eta", "η"),
+    ("theta", "θ"),
+    ("iota", "ι"),
+    ("kappa", "κ"),
+    ("lambda", "λ"),
+    ("mu", "μ"),
+    ("nu", "ν"),
+    ("xi", "ξ"),
+    ("pi", "π"),
+    ("rho", "ρ"),
+    ("sigma", "σ"),
+    ("tau", "τ"),
+    ("upsilon", "υ"),
+    ("phi", "φ"),
+    ("chi", "χ"),
+    ("psi", "ψ"),
+    ("omega", "ω"),
+];
+
+// CommonMark 中可以被反斜杠转义的字符：所有 ASCII 标点
+pub fn is_escapable(c: char) -> bool {
+    c.is_ascii_punctuation()
+}
+
+// 解码 text 开头的 HTML 实体，返回 (字符, 消耗的字节数)
+pub fn decode_entity(text: &str) -> Option<(String, usize)> {
+    let end = text.find(';')?;
+    let name = text.get(1..end)?;
+
+    if let Some(number) = name.strip_prefix('#') {
+        // &#123; 最多 7 位十进制，&#x1F600; 最多 6 位十六进制
+        let (digits, radix, max_len) = match number.strip_prefix(['x', 'X']) {
+            Some(hex) => (hex, 16, 6),
+            None => (number, 10, 7),
+        };
+        if digits.is_empty() || digits.len() > max_len || !digits.chars().all(|c| c.is_digit(radix)) {
+            return None;
+        }
+        // 无效码点和控制字符（NUL、ESC、BEL、DEL、C1 等）替换为 U+FFFD，避免文字中混入终端控制序列
+        let c = u32::from_str_radix(digits, radix)
+            .ok()
+            .and_then(char::from_u32)
+            .filter(|c| !c.is_control())
+            .unwrap_or('\u{fffd}');
+        return Some((c.to_string(), end + 1));
+    }
+
+    NAMED_ENTITIES
+        .iter()
+        .find(|(entity, _)| *entity == name)
+        .map(|(_, decoded)| (decoded.to_string(), end + 1))
+}
+
+// 处理链接地址等纯文本中的转义和实体
+pub fn unescape(text: &str) -> String {
+    let mut result = String::new();
+    let mut pos = 0;
+    while let Some(c) = text[pos..].chars().next() {
+        if c == '\\' && let Some(next) = text[pos + 1..].chars().next().filter(|&n| is_escapable(n)) {
+            result.push(next);
+            pos += 1 + next.len_utf8();
+        } else if c == '&' && let Some((decoded, len)) = decode_entity(&text[pos..]) {
+            result.push_str(&decoded);
+            pos += len;
+        } else {
+            result.push(c);
+            pos += c.len_utf8();
+        }
+    }
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn named_entities() {
+        assert_eq!(decode_entity("&amp; rest"), Some(("&".to_string(), 5)));
+        assert_eq!(decode_entity("&copy;"), Some(("©".to_string(), 6)));
+        assert_eq!(decode_entity("&nosuch;"), None);
+        assert_eq!(decode_entity("&amp"), None);
+        // 实体名区分大小写
+        assert_eq!(decode_entity("&AMP;"), None);
+    }
+
+    #[test]
+    fn numeric_entities() {
+        assert_eq!(decode_entity("&#35;"), Some(("#".to_string(), 5)));
+        assert_eq!(decode_entity("&#1234;"), Some(("Ӓ".to_string(), 7)));
+        assert_eq!(decode_entity("&#x1F600;"), Some(("😀".to_string(), 9)));
+        assert_eq!(decode_entity("&#X22;"), Some(("\"".to_string(), 6)));
+        // NUL、超出范围和代理码点替换为 U+FFFD
+        assert_eq!(decode_entity("&#0;"), Some(("\u{fffd}".to_string(), 4)));
+        assert_eq!(decode_entity("&#xD800;"), Some(("\u{fffd}".to_string(), 8)));
+        // 控制字符不能解码出来，否则可以注入终端转义序列
+        assert_eq!(decode_entity("&#27;[31m"), Some(("\u{fffd}".to_string(), 5)));
+        assert_eq!(decode_entity("&#7;"), Some(("\u{fffd}".to_string(), 4)));
+        assert_eq!(decode_entity("&#x7F;"), Some(("\u{fffd}".to_string(), 6)));
+        assert_eq!(decode_entity("&#x9B;"), Some(("\u{fffd}".to_string(), 6)));
+        assert_eq!(decode_entity("&#;"), None);
+        assert_eq!(decode_entity("&#x;"), None);
+        assert_eq!(decode_entity("&#87654321;"), None);
+        assert_eq!(decode_entity("&#xabcdef0;"), None);
+        assert_eq!(decode_entity("&#12a;"), None);
+    }
+
+    #[test]
+    fn unescape_text() {
+        assert_eq!(unescape(r"\*not emphasis\*"), "*not emphasis*");
+        assert_eq!(unescape(r"\a\\b"), r"\a\b");
+        assert_eq!(unescape("a&amp;b&#33;"), "a&b!");
+        assert_eq!(unescape("&unknown; 中文"), "&unknown; 中文");
+    }
+}
@@ -0,0 +1,55 @@
+// 提取回答中的围栏代码块：保存到目录，或在回答结束后把第 N 个代码块原样输出到 stdout
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+
+#[derive(Clone)]
+pub struct CodeBlock {
+    pub lang: String,
+    pub content: String, // 原始代码，不含高亮，换行统一为 \n
+}
+
+// 按语言选择文件扩展名；未知的语言名本身像扩展名时直接使用
+fn extension(lang: &str) -> String {
+    let lang = lang.to_ascii_lowercase();
+    let ext = match lang.as_str() {
+        "" | "text" | "txt" | "plain" | "plaintext" => "txt",
+        "rust" | "rs" => "rs",
+        "python" | "py" | "python3" | "py3" => "py",
+        "javascript" | "js" | "node" => "js",
+        "typescript" | "ts" => "ts",
+        "go" | "golang" => "go",
+        "sh" | "bash" | "shell" | "zsh" | "ksh" | "console" | "shellscript" => "sh",
+        "powershell" | "ps1" | "pwsh" => "ps1",
+        "json" | "jsonc" | "json5" => "json",
+        "yaml" | "yml" => "yaml",
+        "toml" => "toml",
+        "ini" | "cfg" | "conf" => "ini",
+        "sql" | "mysql" | "postgresql" | "postgres" | "psql" | "sqlite" | "plsql" => "sql",
+        "c" | "h" => "c",
+        "cpp" | "c++" | "cc" | "cxx" | "hpp" => "cpp",
+        "java" => "java",
+        "kotlin" | "kt" => "kt",
+        "ruby" | "rb" => "rb",
+        "html" | "htm" => "html",
+        "markdown" | "md" => "md",
+        "diff" | "patch" | "udiff" => "diff",
+        "dockerfile" | "docker" => "dockerfile",
+        "makefile" | "make" => "mk",
+        other if other.len() <= 10 && other.chars().all(|c| c.is_ascii_alphanumeric()) => other,
+        _ => "txt",
+    };
+    ext.to_string()
+}
+
+// 写入 dir/code-1.rs、dir/code-2.sh ...，返回写入的文件路径
+pub fn save_all(blocks: &[CodeBlock], dir: &Path) -> io::Result<Vec<PathBuf>> {
+    fs::create_dir_all(dir)?;
+    let mut paths = Vec::new();
+    for (i, block) in blocks.iter().enumerate() {
+        let path = dir.join(format!("code-{}.{}", i + 1, extension(&block.lang)));
+        fs::write(&path, &block.content)?;
+        paths.push(path);
+    }
+    Ok(paths)
+}
@@ -0,0 +1,169 @@
+// 围栏代码块：``` 或 ~~~ 开始（至少 3 个），只有同种字符、长度不小于开始围栏的行才能结束
+// 开始围栏后的信息字符串解析为语言和属性，如 ```rust title="main.rs" 或 ```rust,ignore
+
+#[derive(Clone)]
+pub struct Fence {
+    ch: char,
+    len: usize,
+    indent: usize, // 开始围栏的缩进，代码行去掉同样多的前导空格
+}
+
+#[derive(Clone, Default)]
+pub struct InfoString {
+    pub lang: String,
+    pub attributes: Vec<(String, String)>, // key=value，单独的标记值为空
+}
+
+// 围栏前最多 3 个空格，返回 (缩进, 剩余内容)
+fn split_indent(line: &str) -> Option<(usize, &str)> {
+    let rest = line.trim_start_matches(' ');
+    let indent = line.len() - rest.len();
+    (indent <= 3).then_some((indent, rest))
+}
+
+// 去掉最多 count 个前导空格
+pub fn strip_spaces(line: &str, count: usize) -> &str {
+    let spaces = line.len() - line.trim_start_matches(' ').len();
+    &line[spaces.min(count)..]
+}
+
+// 解析开始围栏，返回围栏和信息字符串
+pub fn parse_opening(line: &str) -> Option<(Fence, InfoString)> {
+    let (indent, rest) = split_indent(line)?;
+    let ch = rest.chars().next().filter(|&c| c == '`' || c == '~')?;
+    let len = rest.chars().take_while(|&c| c == ch).count();
+    if len < 3 {
+        return None;
+    }
+    let info = rest[len..].trim();
+    // 反引号围栏的信息字符串里不能有反引号，否则是行内代码
+    if ch == '`' && info.contains('`') {
+        return None;
+    }
+    Some((Fence { ch, len, indent }, parse_info_string(info)))
+}
+
+impl Fence {
+    // 结束围栏：同种字符，长度不小于开始围栏，后面只能有空白
+    pub fn is_closed_by(&self, line: &str) -> bool {
+        let Some((_, rest)) = split_indent(line) else {
+            return false;
+        };
+        let len = rest.chars().take_while(|&c| c == self.ch).count();
+        len >= self.len && rest[len..].trim().is_empty()
+    }
+
+    // 代码行去掉最多与开始围栏相同数量的前导空格
+    pub fn strip_indent<'a>(&self, line: &'a str) -> &'a str {
+        strip_spaces(line, self.indent)
+    }
+}
+
+// 信息字符串：第一个词是语言（{.lang} 和 lang,flag 形式也可以），其余是属性
+fn parse_info_string(info: &str) -> InfoString {
+    let info = info.strip_prefix('{').and_then(|i| i.strip_suffix('}')).unwrap_or(info);
+    let mut words = split_words(info).into_iter();
+    let mut result = InfoString::default();
+
+    if let Some(first) = words.next() {
+        if first.contains('=') {
+            result.attributes.push(parse_attribute(&first));
+        } else {
+            let mut parts = first.split(',');
+            let lang = parts.next().unwrap_or("");
+            result.lang = lang.strip_prefix('.').unwrap_or(lang).to_string();
+            for flag in parts.filter(|p| !p.is_empty()) {
+                result.attributes.push(parse_attribute(flag));
+            }
+        }
+    }
+    for word in words {
+        result.attributes.push(parse_attribute(&word));
+    }
+    result
+}
+
+fn parse_attribute(word: &str) -> (String, String) {
+    match word.split_once('=') {
+        Some((key, value)) => (key.to_string(), value.trim_matches('"').to_string()),
+        None => (word.to_string(), String::new()),
+    }
+}
+
+// 按空白分词，双引号内的空白不分割
+fn split_words(text: &str) -> Vec<String> {
+    let mut words = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    for c in text.chars() {
+        match c {
+            '"' => {
+                in_quotes = !in_quotes;
+                current.push(c);
+            }
+            c if c.is_whitespace() && !in_quotes => {
+                if !current.is_empty() {
+                    words.push(std::mem::take(&mut current));
+                }
+            }
+            _ => current.push(c),
+        }
+    }
+    if !current.is_empty() {
+        words.push(current);
+    }
+    words
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn opening_fence() {
+        let (fence, info) = parse_opening("```rust").unwrap();
+        assert_eq!((fence.ch, fence.len, fence.indent), ('`', 3, 0));
+        assert_eq!(info.lang, "rust");
+
+        let (fence, info) = parse_opening("  ~~~~ python").unwrap();
+        assert_eq!((fence.ch, fence.len, fence.indent), ('~', 4, 2));
+        assert_eq!(info.lang, "python");
+
+        assert!(parse_opening("``").is_none());
+        assert!(parse_opening("    ```").is_none());
+        // 反引号围栏的信息字符串不能含反引号，~~~ 可以
+        assert!(parse_opening("``` a`b").is_none());
+        assert!(parse_opening("~~~ a`b").is_some());
+    }
+
+    #[test]
+    fn info_string() {
+        let (_, info) = parse_opening(r#"```rust title="main.rs" linenos"#).unwrap();
+        assert_eq!(info.lang, "rust");
+        assert_eq!(
+            info.attributes,
+            vec![("title".to_string(), "main.rs".to_string()), ("linenos".to_string(), String::new())]
+        );
+
+        let (_, info) = parse_opening("```rust,ignore").unwrap();
+        assert_eq!(info.lang, "rust");
+        assert_eq!(info.attributes, vec![("ignore".to_string(), String::new())]);
+
+        let (_, info) = parse_opening("``` {.python}").unwrap();
+        assert_eq!(info.lang, "python");
+
+        let (_, info) = parse_opening(r#"```title="a b.txt""#).unwrap();
+        assert_eq!(info.lang, "");
+        assert_eq!(info.attributes, vec![("title".to_string(), "a b.txt".to_string())]);
+    }
+
+    #[test]
+    fn closing_fence() {
+        let (fence, _) = parse_opening("````").unwrap();
+        assert!(fence.is_closed_by("````"));
+        assert!(fence.is_closed_by("   `````  "));
+        assert!(!fence.is_closed_by("```"));
+        assert!(!fence.is_closed_by("~~~~"));
+        assert!(!fence.is_closed_by("```` rust"));
+    }
+}
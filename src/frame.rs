@@ -0,0 +1,115 @@
+// 带边框的代码块：按终端宽度画出完整的框，语言显示在上边框中
+// 可选行号；制表符展开为空格；超长的行折行或截断，并显示续行标记
+use crate::diff::CLEAR_TO_EOL;
+use crate::width::{self, display_width};
+use crate::theme::Theme;
+use crate::{GRAY, PLAIN_BOLD, RESET};
+
+const TAB_WIDTH: usize = 4;
+const MIN_WIDTH: usize = 20; // 终端过窄时仍保留的最小框宽
+
+#[derive(Clone, Copy)]
+pub struct FrameStyle {
+    pub line_numbers: bool,
+    pub truncate: bool, // 超长的行截断，否则折行
+}
+
+#[derive(Clone)]
+pub struct CodeFrame {
+    style: FrameStyle,
+    width: usize,       // 整个框的显示宽度（含两侧边框）
+    line_number: usize, // 已输出的代码行数
+    border: String,     // 边框和行号的样式
+    badge: String,      // 语言标识的样式
+}
+
+impl CodeFrame {
+    pub fn new(style: FrameStyle, width: usize, theme: &Theme) -> Self {
+        Self {
+            style,
+            width: width.max(MIN_WIDTH),
+            line_number: 0,
+            border: theme.code_border.clone(),
+            badge: format!("{}{}", PLAIN_BOLD, theme.code),
+        }
+    }
+
+    // ╭─ rust ──────╮，没有语言时只画横线
+    pub fn top(&self, badge: &str) -> String {
+        if badge.is_empty() {
+            return format!("{}╭{}╮{}\n", self.border, "─".repeat(self.width - 2), RESET);
+        }
+        // 语言标识过长时截断，至少保留一段横线
+        let (badge, _) = width::split_at_width(badge, self.width - 6);
+        let fill = self.width - 5 - display_width(&badge);
+        format!("{}╭─ {}{}{}{} {}╮{}\n", self.border, self.badge, badge, RESET, self.border, "─".repeat(fill), RESET)
+    }
+
+    pub fn bottom(&self) -> String {
+        format!("{}╰{}╯{}\n", self.border, "─".repeat(self.width - 2), RESET)
+    }
+
+    // 输出一行已高亮的代码（不含换行符），折行时可能占多行
+    pub fn line(&mut self, code: &str) -> String {
+        self.line_number += 1;
+        let number_width = if self.style.line_numbers { self.line_number.to_string().len().max(3) + 1 } else { 0 };
+        let content_width = self.width.saturating_sub(4 + number_width).max(1);
+
+        let mut rows = Vec::new();
+        if self.style.truncate {
+            let content = if display_width(code) > content_width {
+                let (head, _) = width::split_at_width(code, content_width - 1);
+                format!("{}{}…{}", head, GRAY, RESET)
+            } else {
+                code.to_string()
+            };
+            rows.push(content);
+        } else {
+            // 续行以 ↪ 开头，占用两列
+            let (head, mut rest) = width::split_at_width(code, content_width);
+            rows.push(head);
+            while display_width(&rest) > 0 {
+                let (head, tail) = width::split_at_width(&rest, content_width.saturating_sub(2).max(1));
+                rows.push(format!("{}↪{} {}", GRAY, RESET, head));
+                rest = tail;
+            }
+        }
+
+        let mut result = String::new();
+        for (i, content) in rows.iter().enumerate() {
+            let number = match (self.style.line_numbers, i) {
+                (false, _) => String::new(),
+                (true, 0) => format!("{}{:>w$}{} ", self.border, self.line_number, RESET, w = number_width - 1),
+                (true, _) => " ".repeat(number_width),
+            };
+            // 填充到行尾的背景色（diff 的增删行）只填充到右边框
+            let padding = " ".repeat(content_width.saturating_sub(display_width(content)));
+            let content = match content.split_once(CLEAR_TO_EOL) {
+                Some((before, after)) => format!("{}{}{}", before, padding, after),
+                None => format!("{}{}{}", content, RESET, padding),
+            };
+            result.push_str(&format!("{}│{} {}{} {}│{}\n", self.border, RESET, number, content, self.border, RESET));
+        }
+        result
+    }
+}
+
+// 制表符展开为空格，对齐到下一个制表位
+pub fn expand_tabs(line: &str) -> String {
+    if !line.contains('\t') {
+        return line.to_string();
+    }
+    let mut result = String::new();
+    let mut column = 0;
+    for c in line.chars() {
+        if c == '\t' {
+            let spaces = TAB_WIDTH - column % TAB_WIDTH;
+            result.push_str(&" ".repeat(spaces));
+            column += spaces;
+        } else {
+            result.push(c);
+            column += display_width(c.encode_utf8(&mut [0; 4]));
+        }
+    }
+    result
+}
@@ -0,0 +1,649 @@
+// 代码块语法高亮：逐行处理的简单词法分析器
+// 跨行的块注释和多行字符串状态保存在 Highlighter 中，随代码块逐行延续
+use crate::theme::SyntaxTheme;
+use crate::RESET;
+
+#[derive(Clone, Copy, PartialEq)]
+enum Lang {
+    Rust,
+    Python,
+    JavaScript, // 包括 TypeScript
+    Go,
+    Shell,
+    Json,
+    Yaml,
+    Toml,
+    Sql,
+    C, // 包括 C++
+}
+
+#[derive(Clone, Copy)]
+enum TokenKind {
+    Keyword,
+    Type,
+    Literal, // true / false / null 等
+    Number,
+    String,
+    Comment,
+    Function,  // 函数调用、宏
+    Attribute, // 属性、装饰器、预处理指令
+    Key,       // JSON / YAML / TOML 的键
+    Variable,  // shell 变量、Rust 生命周期
+}
+
+impl TokenKind {
+    fn style(self, colors: &SyntaxTheme) -> &str {
+        match self {
+            TokenKind::Keyword => &colors.keyword,
+            TokenKind::Type => &colors.type_name,
+            TokenKind::Literal => &colors.literal,
+            TokenKind::Number => &colors.number,
+            TokenKind::String => &colors.string,
+            TokenKind::Comment => &colors.comment,
+            TokenKind::Function => &colors.function,
+            TokenKind::Attribute => &colors.attribute,
+            TokenKind::Key => &colors.key,
+            TokenKind::Variable => &colors.variable,
+        }
+    }
+}
+
+// 跨行的词法状态
+#[derive(Clone, PartialEq)]
+enum State {
+    Normal,
+    Comment { depth: usize },                 // 块注释，Rust 的块注释可以嵌套
+    String { close: String, escapes: bool },  // 多行字符串
+}
+
+// 每种语言的词法规则
+struct Syntax {
+    keywords: &'static [&'static str],
+    types: &'static [&'static str],
+    literals: &'static [&'static str],
+    line_comments: &'static [&'static str],
+    block_comment: Option<(&'static str, &'static str)>,
+    // 字符串定界符：(开始/结束, 是否处理反斜杠转义, 是否可以跨行)，长的放前面
+    quotes: &'static [(&'static str, bool, bool)],
+}
+
+const RUST: Syntax = Syntax {
+    keywords: &[
+        "as", "async", "await", "break", "const", "continue", "crate", "dyn", "else", "enum", "extern", "fn", "for",
+        "if", "impl", "in", "let", "loop", "match", "mod", "move", "mut", "pub", "ref", "return", "self", "Self",
+        "static", "struct", "super", "trait", "type", "unsafe", "use", "where", "while", "yield",
+    ],
+    types: &[
+        "i8", "i16", "i32", "i64", "i128", "isize", "u8", "u16", "u32", "u64", "u128", "usize", "f32", "f64", "bool",
+        "char", "str",
+    ],
+    literals: &["true", "false", "None", "Some", "Ok", "Err"],
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &[("\"", true, true)],
+};
+
+const PYTHON: Syntax = Syntax {
+    keywords: &[
+        "and", "as", "assert", "async", "await", "break", "class", "continue", "def", "del", "elif", "else", "except",
+        "finally", "for", "from", "global", "if", "import", "in", "is", "lambda", "match", "case", "nonlocal", "not",
+        "or", "pass", "raise", "return", "try", "while", "with", "yield", "self", "cls",
+    ],
+    types: &["int", "float", "str", "bytes", "bool", "list", "dict", "set", "tuple", "object", "type"],
+    literals: &["True", "False", "None"],
+    line_comments: &["#"],
+    block_comment: None,
+    quotes: &[("\"\"\"", true, true), ("'''", true, true), ("\"", true, false), ("'", true, false)],
+};
+
+const JAVASCRIPT: Syntax = Syntax {
+    keywords: &[
+        "abstract", "as", "async", "await", "break", "case", "catch", "class", "const", "continue", "debugger",
+        "declare", "default", "delete", "do", "else", "enum", "export", "extends", "finally", "for", "from", "function",
+        "get", "if", "implements", "import", "in", "instanceof", "interface", "keyof", "let", "namespace", "new", "of",
+        "private", "protected", "public", "readonly", "return", "set", "static", "super", "switch", "this", "throw",
+        "try", "type", "typeof", "var", "void", "while", "with", "yield",
+    ],
+    types: &["any", "boolean", "never", "number", "object", "string", "symbol", "unknown", "bigint"],
+    literals: &["true", "false", "null", "undefined", "NaN", "Infinity"],
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &[("`", true, true), ("\"", true, false), ("'", true, false)],
+};
+
+const GO: Syntax = Syntax {
+    keywords: &[
+        "break", "case", "chan", "const", "continue", "default", "defer", "else", "fallthrough", "for", "func", "go",
+        "goto", "if", "import", "interface", "map", "package", "range", "return", "select", "struct", "switch", "type",
+        "var",
+    ],
+    types: &[
+        "bool", "byte", "complex64", "complex128", "error", "float32", "float64", "int", "int8", "int16", "int32",
+        "int64", "rune", "string", "uint", "uint8", "uint16", "uint32", "uint64", "uintptr", "any",
+    ],
+    literals: &["true", "false", "nil", "iota"],
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &[("`", false, true), ("\"", true, false), ("'", true, false)],
+};
+
+const SHELL: Syntax = Syntax {
+    keywords: &[
+        "if", "then", "else", "elif", "fi", "for", "while", "until", "do", "done", "case", "esac", "in", "function",
+        "return", "local", "export", "readonly", "declare", "select", "time",
+    ],
+    types: &[],
+    literals: &["true", "false"],
+    line_comments: &["#"],
+    block_comment: None,
+    quotes: &[("\"", true, true), ("'", false, true)],
+};
+
+const JSON: Syntax = Syntax {
+    keywords: &[],
+    types: &[],
+    literals: &["true", "false", "null"],
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &[("\"", true, false)],
+};
+
+const YAML: Syntax = Syntax {
+    keywords: &[],
+    types: &[],
+    literals: &["true", "false", "null", "yes", "no", "on", "off", "True", "False", "Null", "~"],
+    line_comments: &["#"],
+    block_comment: None,
+    quotes: &[("\"", true, false), ("'", false, false)],
+};
+
+const TOML: Syntax = Syntax {
+    keywords: &[],
+    types: &[],
+    literals: &["true", "false", "inf", "nan"],
+    line_comments: &["#"],
+    block_comment: None,
+    quotes: &[("\"\"\"", true, true), ("'''", false, true), ("\"", true, false), ("'", false, false)],
+};
+
+// SQL 关键字不区分大小写，这里用小写
+const SQL: Syntax = Syntax {
+    keywords: &[
+        "add", "all", "alter", "and", "as", "asc", "begin", "between", "by", "case", "commit", "constraint", "create",
+        "cross", "database", "default", "delete", "desc", "distinct", "drop", "else", "end", "exists", "foreign",
+        "from", "full", "group", "having", "if", "in", "index", "inner", "insert", "into", "is", "join", "key", "left",
+        "like", "limit", "not", "offset", "on", "or", "order", "outer", "primary", "references", "returning", "right",
+        "rollback", "select", "set", "table", "then", "transaction", "union", "unique", "update", "using", "values",
+        "view", "when", "where", "with",
+    ],
+    types: &[
+        "bigint", "blob", "boolean", "char", "date", "datetime", "decimal", "double", "float", "int", "integer", "json",
+        "jsonb", "numeric", "real", "serial", "smallint", "text", "time", "timestamp", "uuid", "varchar",
+    ],
+    literals: &["true", "false", "null"],
+    line_comments: &["--"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &[("'", false, true), ("\"", false, false)],
+};
+
+const C: Syntax = Syntax {
+    keywords: &[
+        "auto", "break", "case", "catch", "class", "const", "constexpr", "continue", "default", "delete", "do", "else",
+        "enum", "explicit", "extern", "for", "friend", "goto", "if", "inline", "mutable", "namespace", "new",
+        "noexcept", "operator", "override", "private", "protected", "public", "register", "return", "sizeof",
+        "static", "static_cast", "dynamic_cast", "reinterpret_cast", "const_cast", "struct", "switch", "template",
+        "this", "throw", "try", "typedef", "typename", "union", "using", "virtual", "volatile", "while",
+    ],
+    types: &[
+        "bool", "char", "double", "float", "int", "long", "short", "signed", "unsigned", "void", "size_t", "int8_t",
+        "int16_t", "int32_t", "int64_t", "uint8_t", "uint16_t", "uint32_t", "uint64_t", "string", "vector",
+    ],
+    literals: &["true", "false", "NULL", "nullptr"],
+    line_comments: &["//"],
+    block_comment: Some(("/*", "*/")),
+    quotes: &[("\"", true, false), ("'", true, false)],
+};
+
+#[derive(Clone)]
+pub struct Highlighter {
+    lang: Lang,
+    state: State,
+    colors: SyntaxTheme,
+}
+
+impl Highlighter {
+    // 根据代码块的语言名（或文件扩展名）创建，不支持的语言返回 None
+    pub fn new(name: &str, colors: &SyntaxTheme) -> Option<Self> {
+        let lang = match name.to_ascii_lowercase().as_str() {
+            "rust" | "rs" => Lang::Rust,
+            "python" | "py" | "python3" | "py3" | "pyi" => Lang::Python,
+            "javascript" | "js" | "jsx" | "mjs" | "cjs" | "typescript" | "ts" | "tsx" | "mts" | "cts" => Lang::JavaScript,
+            "go" | "golang" => Lang::Go,
+            "sh" | "bash" | "shell" | "zsh" | "ksh" | "console" | "shellscript" => Lang::Shell,
+            "json" | "jsonc" | "json5" => Lang::Json,
+            "yaml" | "yml" => Lang::Yaml,
+            "toml" => Lang::Toml,
+            "sql" | "mysql" | "postgresql" | "postgres" | "psql" | "sqlite" | "plsql" => Lang::Sql,
+            "c" | "h" | "cpp" | "c++" | "cc" | "cxx" | "hpp" | "hh" | "hxx" | "objc" => Lang::C,
+            _ => return None,
+        };
+        Some(Self {
+            lang,
+            state: State::Normal,
+            colors: colors.clone(),
+        })
+    }
+
+    fn syntax(&self) -> &'static Syntax {
+        match self.lang {
+            Lang::Rust => &RUST,
+            Lang::Python => &PYTHON,
+            Lang::JavaScript => &JAVASCRIPT,
+            Lang::Go => &GO,
+            Lang::Shell => &SHELL,
+            Lang::Json => &JSON,
+            Lang::Yaml => &YAML,
+            Lang::Toml => &TOML,
+            Lang::Sql => &SQL,
+            Lang::C => &C,
+        }
+    }
+
+    // 高亮一行代码，保留行尾的换行符
+    pub fn highlight_line(&mut self, line: &str) -> String {
+        let body = line.trim_end_matches(['\r', '\n']);
+        let mut out = String::new();
+        let mut pos = self.resume(body, &mut out);
+        while pos < body.len() {
+            pos = self.next_token(body, pos, &mut out);
+        }
+        out.push_str(&line[body.len()..]);
+        out
+    }
+
+    // 继续上一行未结束的块注释或多行字符串，返回处理到的位置
+    fn resume(&mut self, body: &str, out: &mut String) -> usize {
+        match self.state.clone() {
+            State::Normal => 0,
+            State::Comment { depth } => {
+                let end = self.comment_end(body, 0, depth);
+                self.paint(out, TokenKind::Comment, &body[..end]);
+                end
+            }
+            State::String { close, escapes } => {
+                let end = match find_close(body, 0, &close, escapes) {
+                    Some(end) => {
+                        self.state = State::Normal;
+                        end
+                    }
+                    None => body.len(),
+                };
+                self.paint(out, TokenKind::String, &body[..end]);
+                end
+            }
+        }
+    }
+
+    // 从 start 开始查找块注释的结束位置（处理嵌套），找不到时进入跨行状态
+    fn comment_end(&mut self, body: &str, start: usize, depth: usize) -> usize {
+        let (open, close) = self.syntax().block_comment.unwrap();
+        let nested = self.lang == Lang::Rust;
+        let mut depth = depth;
+        let mut pos = start;
+        while pos < body.len() {
+            if body[pos..].starts_with(close) {
+                depth -= 1;
+                pos += close.len();
+                if depth == 0 {
+                    self.state = State::Normal;
+                    return pos;
+                }
+            } else if nested && body[pos..].starts_with(open) {
+                depth += 1;
+                pos += open.len();
+            } else {
+                pos += body[pos..].chars().next().unwrap().len_utf8();
+            }
+        }
+        self.state = State::Comment { depth };
+        body.len()
+    }
+
+    // 处理 pos 处的一个词法单元，返回下一个位置
+    fn next_token(&mut self, body: &str, pos: usize, out: &mut String) -> usize {
+        let syntax = self.syntax();
+        let rest = &body[pos..];
+        let c = rest.chars().next().unwrap();
+        let before = body[..pos].chars().next_back();
+        let line_start = body[..pos].trim().is_empty();
+
+        if c.is_whitespace() {
+            out.push(c);
+            return pos + c.len_utf8();
+        }
+
+        // 行注释；# 注释需要位于行首或空白之后（shell 的 ${#var}、YAML 的 a#b 不是注释）
+        for prefix in syntax.line_comments {
+            if rest.starts_with(prefix) && (*prefix != "#" || before.is_none_or(char::is_whitespace)) {
+                self.paint(out, TokenKind::Comment, rest);
+                return body.len();
+            }
+        }
+        if let Some((open, _)) = syntax.block_comment
+            && rest.starts_with(open)
+        {
+            let end = self.comment_end(body, pos + open.len(), 1);
+            self.paint(out, TokenKind::Comment, &body[pos..end]);
+            return end;
+        }
+
+        if let Some(end) = self.language_token(body, pos, line_start, out) {
+            return end;
+        }
+
+        // 字符串，包括 Rust 的 r#"..."# 和 Python 的 f"..." 等前缀
+        if let Some((prefix_len, close, escapes, multiline)) = self.string_start(rest) {
+            let start = pos + prefix_len;
+            let end = match find_close(body, start, &close, escapes) {
+                Some(end) => end,
+                None => {
+                    if multiline {
+                        self.state = State::String { close, escapes };
+                    }
+                    body.len()
+                }
+            };
+            // JSON / YAML 中后面跟着冒号的字符串是键
+            let is_key = matches!(self.lang, Lang::Json | Lang::Yaml) && body[end..].trim_start().starts_with(':');
+            self.paint(out, if is_key { TokenKind::Key } else { TokenKind::String }, &body[pos..end]);
+            return end;
+        }
+
+        // 数字：不能紧跟在标识符后面
+        if c.is_ascii_digit() && !before.is_some_and(is_ident_char) {
+            let mut end = pos;
+            for (i, d) in rest.char_indices() {
+                // 1..10 中的 .. 不属于数字
+                if !(d.is_ascii_alphanumeric() || d == '_' || d == '.' && !rest[i + 1..].starts_with('.')) {
+                    break;
+                }
+                end = pos + i + d.len_utf8();
+            }
+            self.paint(out, TokenKind::Number, &body[pos..end]);
+            return end;
+        }
+
+        // 标识符
+        if c.is_alphabetic() || c == '_' || c == '$' && self.lang == Lang::JavaScript {
+            let len = rest
+                .char_indices()
+                .find(|&(_, d)| !(is_ident_char(d) || d == '$' && self.lang == Lang::JavaScript))
+                .map_or(rest.len(), |(i, _)| i);
+            let word = &rest[..len];
+            let after = &rest[len..];
+            let lookup = if self.lang == Lang::Sql { word.to_ascii_lowercase() } else { word.to_string() };
+            let kind = if syntax.keywords.contains(&lookup.as_str()) {
+                Some(TokenKind::Keyword)
+            } else if syntax.literals.contains(&lookup.as_str()) {
+                Some(TokenKind::Literal)
+            } else if syntax.types.contains(&lookup.as_str()) {
+                Some(TokenKind::Type)
+            } else if self.lang == Lang::Rust && after.starts_with('!') && !after.starts_with("!=") {
+                // 宏调用，连同 ! 一起高亮
+                self.paint(out, TokenKind::Function, &rest[..len + 1]);
+                return pos + len + 1;
+            } else if after.starts_with('(') && !matches!(self.lang, Lang::Shell | Lang::Json | Lang::Yaml | Lang::Toml) {
+                Some(TokenKind::Function)
+            } else if c.is_uppercase() && matches!(self.lang, Lang::Rust | Lang::Go | Lang::JavaScript | Lang::Python | Lang::C) {
+                Some(TokenKind::Type)
+            } else {
+                None
+            };
+            match kind {
+                Some(kind) => self.paint(out, kind, word),
+                None => out.push_str(word),
+            }
+            return pos + len;
+        }
+
+        out.push(c);
+        pos + c.len_utf8()
+    }
+
+    // 各语言特有的词法单元：属性、装饰器、变量、预处理指令、配置文件的键等
+    fn language_token(&self, body: &str, pos: usize, line_start: bool, out: &mut String) -> Option<usize> {
+        let rest = &body[pos..];
+        let c = rest.chars().next()?;
+        let end = match self.lang {
+            // #[derive(...)] / #![allow(...)]
+            Lang::Rust if rest.starts_with("#[") || rest.starts_with("#![") => {
+                let end = rest.find(']').map_or(body.len(), |i| pos + i + 1);
+                self.paint(out, TokenKind::Attribute, &body[pos..end]);
+                end
+            }
+            // 生命周期 'a（字符字面量 'a' 交给字符串处理）
+            Lang::Rust if c == '\'' && self.string_start(rest).is_none() => {
+                let len = 1 + rest[1..].find(|d: char| !is_ident_char(d)).unwrap_or(rest.len() - 1);
+                self.paint(out, TokenKind::Variable, &rest[..len]);
+                pos + len
+            }
+            // @decorator
+            Lang::Python | Lang::JavaScript if c == '@' => {
+                let len = 1 + rest[1..].find(|d: char| !(is_ident_char(d) || d == '.')).unwrap_or(rest.len() - 1);
+                self.paint(out, TokenKind::Attribute, &rest[..len]);
+                pos + len
+            }
+            // $VAR、${VAR}、$1、$?
+            Lang::Shell if c == '$' => {
+                let len = if rest[1..].starts_with('{') {
+                    rest.find('}').map_or(rest.len(), |i| i + 1)
+                } else if rest[1..].starts_with(|d: char| "?!#@*$-0123456789".contains(d)) {
+                    2
+                } else {
+                    1 + rest[1..].find(|d: char| !is_ident_char(d)).unwrap_or(rest.len() - 1)
+                };
+                if len == 1 {
+                    return None;
+                }
+                self.paint(out, TokenKind::Variable, &rest[..len]);
+                pos + len
+            }
+            // #include <stdio.h> 等预处理指令
+            Lang::C if c == '#' && line_start => {
+                let name = rest[1..].trim_start_matches(' ');
+                let len = rest.len() - name.len() + name.find(|d: char| !d.is_alphabetic()).unwrap_or(name.len());
+                self.paint(out, TokenKind::Attribute, &rest[..len]);
+                pos + len
+            }
+            Lang::C if c == '<' && body.trim_start().starts_with("#include") => {
+                let end = rest.find('>').map_or(body.len(), |i| pos + i + 1);
+                self.paint(out, TokenKind::String, &body[pos..end]);
+                end
+            }
+            // [table] / [[array]]
+            Lang::Toml if c == '[' && line_start => {
+                let end = rest.rfind(']').map_or(body.len(), |i| pos + i + 1);
+                self.paint(out, TokenKind::Type, &body[pos..end]);
+                end
+            }
+            // key = value
+            Lang::Toml if line_start && is_ident_char(c) => {
+                let len = rest.find(|d: char| !(is_ident_char(d) || d == '-' || d == '.'))?;
+                if !rest[len..].trim_start().starts_with('=') {
+                    return None;
+                }
+                self.paint(out, TokenKind::Key, &rest[..len]);
+                pos + len
+            }
+            // key: value（行首或列表项 - 之后）
+            Lang::Yaml if (line_start || body[..pos].trim() == "-") && !"\"'-&*".contains(c) => {
+                let colon = rest.find(": ").or_else(|| rest.strip_suffix(':').map(|r| r.len()))?;
+                if rest[..colon].contains(" #") {
+                    return None;
+                }
+                self.paint(out, TokenKind::Key, &rest[..colon]);
+                pos + colon
+            }
+            // &anchor / *alias
+            Lang::Yaml if (c == '&' || c == '*') && rest.len() > 1 => {
+                let len = 1 + rest[1..].find(char::is_whitespace).unwrap_or(rest.len() - 1);
+                self.paint(out, TokenKind::Variable, &rest[..len]);
+                pos + len
+            }
+            _ => return None,
+        };
+        Some(end)
+    }
+
+    // 判断 rest 是否以字符串开始，返回 (前缀长度, 结束定界符, 是否处理转义, 是否可以跨行)
+    fn string_start(&self, rest: &str) -> Option<(usize, String, bool, bool)> {
+        match self.lang {
+            Lang::Rust => {
+                // b"..."、r"..."、r#"..."#、br"..."
+                let prefix = rest.len() - rest.trim_start_matches(['b', 'r']).len();
+                let prefix_str = &rest[..prefix];
+                if prefix <= 2 && (prefix_str.is_empty() || ["b", "r", "br"].contains(&prefix_str)) {
+                    let after = &rest[prefix..];
+                    if prefix_str.ends_with('r') {
+                        let hashes = after.len() - after.trim_start_matches('#').len();
+                        if after[hashes..].starts_with('"') {
+                            return Some((prefix + hashes + 1, format!("\"{}", "#".repeat(hashes)), false, true));
+                        }
+                    } else if after.starts_with('"') {
+                        return Some((prefix + 1, "\"".to_string(), true, true));
+                    } else if let Some(inner) = after.strip_prefix('\'') {
+                        // 字符字面量：'x' 或 '\n'、'\u{..}'
+                        let len = if inner.starts_with('\\') {
+                            inner.find('\'').filter(|&i| i > 1)
+                        } else {
+                            inner.chars().next().map(char::len_utf8).filter(|&l| inner[l..].starts_with('\''))
+                        };
+                        return len.map(|_| (prefix + 1, "'".to_string(), true, false));
+                    }
+                }
+                None
+            }
+            Lang::Python => {
+                // r"..."、f"..."、rb'...' 等前缀
+                let prefix = rest.len() - rest.trim_start_matches(['r', 'R', 'b', 'B', 'u', 'U', 'f', 'F']).len();
+                if prefix > 2 {
+                    return None;
+                }
+                let raw = rest[..prefix].contains(['r', 'R']);
+                self.syntax()
+                    .quotes
+                    .iter()
+                    .find(|(quote, _, _)| rest[prefix..].starts_with(quote))
+                    .map(|&(quote, escapes, multiline)| (prefix + quote.len(), quote.to_string(), escapes && !raw, multiline))
+            }
+            _ => self
+                .syntax()
+                .quotes
+                .iter()
+                .find(|(quote, _, _)| rest.starts_with(quote))
+                .map(|&(quote, escapes, multiline)| (quote.len(), quote.to_string(), escapes, multiline)),
+        }
+    }
+
+    fn paint(&self, out: &mut String, kind: TokenKind, text: &str) {
+        if text.is_empty() {
+            return;
+        }
+        out.push_str(kind.style(&self.colors));
+        out.push_str(text);
+        out.push_str(RESET);
+    }
+}
+
+fn is_ident_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// 从 start 开始查找结束定界符，返回结束定界符之后的位置
+fn find_close(body: &str, start: usize, close: &str, escapes: bool) -> Option<usize> {
+    let mut pos = start;
+    while pos < body.len() {
+        let rest = &body[pos..];
+        if escapes && rest.starts_with('\\') {
+            pos += 1 + rest[1..].chars().next().map_or(0, char::len_utf8);
+        } else if rest.starts_with(close) {
+            return Some(pos + close.len());
+        } else {
+            pos += rest.chars().next().unwrap().len_utf8();
+        }
+    }
+    None
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::theme::Theme;
+
+    // 逐行高亮，与代码块中一行一行到达的方式相同
+    fn highlight(lang: &str, lines: &[&str]) -> (Vec<String>, Highlighter) {
+        let mut highlighter = Highlighter::new(lang, &Theme::dark().syntax).unwrap();
+        let out = lines.iter().map(|line| highlighter.highlight_line(line)).collect();
+        (out, highlighter)
+    }
+
+    fn painted(kind: TokenKind, text: &str) -> String {
+        format!("{}{}{}", kind.style(&Theme::dark().syntax), text, RESET)
+    }
+
+    #[test]
+    fn rust_nested_block_comment() {
+        let (out, highlighter) = highlight("rust", &["let a = 1; /* outer /* inner */", "still comment */ let b = 2;"]);
+        assert!(out[0].ends_with(&painted(TokenKind::Comment, "/* outer /* inner */")));
+        assert!(out[1].starts_with(&painted(TokenKind::Comment, "still comment */")));
+        assert!(out[1].contains(&painted(TokenKind::Keyword, "let")));
+        assert!(highlighter.state == State::Normal);
+
+        let (_, highlighter) = highlight("rust", &["/* a /* b */", "c"]);
+        assert!(highlighter.state == State::Comment { depth: 1 });
+    }
+
+    #[test]
+    fn python_triple_quoted_string() {
+        let (out, highlighter) = highlight("python", &["x = \"\"\"start", "middle \"quoted\" # line", "end\"\"\" + y"]);
+        assert!(out[0].ends_with(&painted(TokenKind::String, "\"\"\"start")));
+        assert_eq!(out[1], painted(TokenKind::String, "middle \"quoted\" # line"));
+        assert!(out[2].starts_with(&painted(TokenKind::String, "end\"\"\"")));
+        assert!(out[2].ends_with(" + y"));
+        assert!(highlighter.state == State::Normal);
+    }
+
+    #[test]
+    fn go_raw_string() {
+        // 反引号字符串不处理转义，行尾的 \ 不会吞掉结束的反引号
+        let (out, highlighter) = highlight("go", &["s := `C:\\dir\\", "// not a comment\\`", "x := 1"]);
+        assert!(out[0].ends_with(&painted(TokenKind::String, "`C:\\dir\\")));
+        assert_eq!(out[1], painted(TokenKind::String, "// not a comment\\`"));
+        assert!(out[2].contains(&painted(TokenKind::Number, "1")));
+        assert!(highlighter.state == State::Normal);
+    }
+
+    #[test]
+    fn rust_raw_string() {
+        let (out, highlighter) = highlight("rust", &["let s = r#\"has \"quotes\"", "still \"inside\" \"#;"]);
+        assert!(out[0].ends_with(&painted(TokenKind::String, "r#\"has \"quotes\"")));
+        assert_eq!(out[1], format!("{};", painted(TokenKind::String, "still \"inside\" \"#")));
+        assert!(highlighter.state == State::Normal);
+    }
+
+    #[test]
+    fn shell_variables_and_comments() {
+        let (out, _) = highlight("sh", &["echo \"$HOME # not a comment\" $USER # comment", "echo ${#arr[@]} $?"]);
+        assert!(out[0].contains(&painted(TokenKind::String, "\"$HOME # not a comment\"")));
+        assert!(out[0].contains(&painted(TokenKind::Variable, "$USER")));
+        assert!(out[0].ends_with(&painted(TokenKind::Comment, "# comment")));
+        assert!(out[1].contains(&painted(TokenKind::Variable, "${#arr[@]}")));
+        assert!(out[1].ends_with(&painted(TokenKind::Variable, "$?")));
+
+        // 双引号字符串可以跨行，下一行开头的 # 仍在字符串中
+        let (out, highlighter) = highlight("sh", &["msg=\"first", "# second\" $x"]);
+        assert!(out[1].starts_with(&painted(TokenKind::String, "# second\"")));
+        assert!(out[1].ends_with(&painted(TokenKind::Variable, "$x")));
+        assert!(highlighter.state == State::Normal);
+    }
+}
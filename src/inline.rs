@@ -0,0 +1,197 @@
+// 行内语法树与输出
+// 输出时维护一个 SGR 样式栈：每个片段结束后先 RESET，再重新应用外层仍然有效的样式，
+// 这样任意嵌套（链接里的斜体里的粗体里的代码）都不会泄漏或被提前截断
+use crate::theme::Theme;
+use crate::{GRAY, RESET};
+
+#[derive(Clone, Copy)]
+pub enum Style {
+    Emphasis,
+    Strong,
+    Strike,
+    Highlight,
+}
+
+pub enum Inline {
+    Text(String),
+    Code(String),
+    Dim(String), // 弱化显示的文字（如无法转换的上下标）
+    Styled(Style, Vec<Inline>),
+    Link {
+        children: Vec<Inline>,
+        url: Option<String>,     // 脚注模式下引用尚未定义时为 None
+        footnote: Option<usize>, // 脚注模式下的编号
+    },
+}
+
+impl Style {
+    fn sgr(self, theme: &Theme) -> &str {
+        match self {
+            Style::Emphasis => &theme.emphasis,
+            Style::Strong => &theme.strong,
+            Style::Strike => &theme.strikethrough,
+            Style::Highlight => &theme.highlight,
+        }
+    }
+}
+
+// 节点中的纯文字内容
+pub fn plain_text(nodes: &[Inline]) -> String {
+    let mut text = String::new();
+    for node in nodes {
+        match node {
+            Inline::Text(t) | Inline::Code(t) | Inline::Dim(t) => text.push_str(t),
+            Inline::Styled(_, children) | Inline::Link { children, .. } => text.push_str(&plain_text(children)),
+        }
+    }
+    text
+}
+
+// 文字就是 URL 的链接（自动链接、裸 URL、链接列表）
+pub fn url_link(shown: &str, url: &str) -> Inline {
+    Inline::Link {
+        children: vec![Inline::Text(encode_controls(shown))],
+        url: Some(url.to_string()),
+        footnote: None,
+    }
+}
+
+// URL 中的控制字符按 UTF-8 字节做百分号编码：ESC、BEL 会提前结束 OSC 8 序列，其余部分会被终端当作输入执行
+fn encode_controls(url: &str) -> String {
+    let mut encoded = String::new();
+    for c in url.chars() {
+        if c.is_control() {
+            for byte in c.encode_utf8(&mut [0; 4]).bytes() {
+                encoded.push_str(&format!("%{:02X}", byte));
+            }
+        } else {
+            encoded.push(c);
+        }
+    }
+    encoded
+}
+
+// 链接文字本身就是 URL（自动链接、裸 URL）时不需要再显示 URL
+fn url_is_visible(label: &str, url: &str) -> bool {
+    let without_scheme = url.split_once("//").or_else(|| url.split_once(':')).map(|(_, rest)| rest);
+    label == url || without_scheme == Some(label)
+}
+
+// 输出语法树；base_style 是外层容器（如标题）的样式，内部片段结束后会恢复它
+pub fn render(nodes: &[Inline], base_style: &str, hyperlinks: bool, theme: &Theme) -> String {
+    let mut emitter = Emitter {
+        hyperlinks,
+        theme,
+        styles: Vec::new(),
+        out: String::new(),
+    };
+    if base_style.is_empty() {
+        emitter.emit_all(nodes);
+    } else {
+        emitter.push(base_style);
+        emitter.emit_all(nodes);
+        emitter.pop(1);
+    }
+    emitter.out
+}
+
+struct Emitter<'a> {
+    hyperlinks: bool,
+    theme: &'a Theme,
+    styles: Vec<&'a str>,
+    out: String,
+}
+
+impl<'a> Emitter<'a> {
+    fn push(&mut self, style: &'a str) {
+        self.styles.push(style);
+        self.out.push_str(style);
+    }
+
+    // 结束最内层的 count 个样式
+    fn pop(&mut self, count: usize) {
+        self.styles.truncate(self.styles.len() - count);
+        self.out.push_str(RESET);
+        self.out.push_str(&self.styles.concat());
+    }
+
+    fn dim(&mut self, text: &str) {
+        self.push(GRAY);
+        self.out.push_str(text);
+        self.pop(1);
+    }
+
+    fn emit_all(&mut self, nodes: &'a [Inline]) {
+        for node in nodes {
+            self.emit(node);
+        }
+    }
+
+    fn emit(&mut self, node: &'a Inline) {
+        match node {
+            Inline::Text(text) => self.out.push_str(text),
+            Inline::Code(code) => {
+                self.push(&self.theme.inline_code);
+                self.out.push_str(&format!(" {} ", code));
+                self.pop(1);
+            }
+            Inline::Dim(text) => self.dim(text),
+            Inline::Styled(style, children) => {
+                self.push(style.sgr(self.theme));
+                self.emit_all(children);
+                self.pop(1);
+            }
+            Inline::Link { children, url, footnote } => {
+                // 支持 OSC 8 的终端输出可点击的超链接
+                let url = url.as_deref().map(encode_controls);
+                let hyperlink = url.as_ref().filter(|_| self.hyperlinks);
+                if let Some(url) = hyperlink {
+                    self.out.push_str(&format!("\x1b]8;;{}\x1b\\", url));
+                }
+                self.push(&self.theme.link);
+                self.emit_all(children);
+                self.pop(1);
+                if hyperlink.is_some() {
+                    self.out.push_str("\x1b]8;;\x1b\\");
+                }
+
+                // 脚注模式显示编号，否则在不支持超链接时用灰色显示 URL
+                match (footnote, &url) {
+                    (Some(number), _) => self.dim(&format!("[{}]", number)),
+                    (None, Some(url)) if hyperlink.is_none() && !url_is_visible(&plain_text(children), url) => {
+                        self.out.push(' ');
+                        self.dim(&format!("({})", url));
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn link(url: &str) -> Inline {
+        Inline::Link {
+            children: vec![Inline::Text("x".to_string())],
+            url: Some(url.to_string()),
+            footnote: None,
+        }
+    }
+
+    #[test]
+    fn hyperlink_url_without_controls() {
+        let out = render(&[link("http://a\x07b\x1b\\c")], "", true, &Theme::dark());
+        assert!(out.starts_with("\x1b]8;;http://a%07b%1B\\c\x1b\\"));
+        assert!(!out.contains('\x07'));
+    }
+
+    #[test]
+    fn fallback_url_without_controls() {
+        let out = render(&[link("http://a\x07b")], "", false, &Theme::dark());
+        assert!(out.contains("(http://a%07b)"));
+        assert!(!out.contains('\x07'));
+    }
+}
@@ -0,0 +1,196 @@
+// 链接解析：行内链接 [text](url)、自动链接 <url>、裸 URL
+use crate::escape;
+
+// 解析行内链接 [text](url "title")，返回 (链接文字, URL, 消耗的字节数)
+pub fn parse_inline_link(text: &str) -> Option<(&str, &str, usize)> {
+    let label_end = find_label_end(text)?;
+    let rest = &text[label_end + 1..];
+    if !rest.starts_with('(') {
+        return None;
+    }
+
+    // URL 中允许成对的括号
+    let mut depth = 0;
+    let mut close = None;
+    for (i, c) in rest.char_indices().skip(1) {
+        match c {
+            '(' => depth += 1,
+            ')' if depth == 0 => {
+                close = Some(i);
+                break;
+            }
+            ')' => depth -= 1,
+            _ => {}
+        }
+    }
+    let close = close?;
+    let destination = rest[1..close].trim();
+    // 去掉可选的标题："url "title"" 只取 url 部分
+    let url = destination.split_whitespace().next().unwrap_or("");
+    let url = url.strip_prefix('<').and_then(|u| u.strip_suffix('>')).unwrap_or(url);
+
+    Some((&text[1..label_end], url, label_end + 1 + close + 1))
+}
+
+// 找到与开头 [ 匹配的 ]，返回其位置
+pub fn find_label_end(text: &str) -> Option<usize> {
+    if !text.starts_with('[') {
+        return None;
+    }
+    let mut depth = 0;
+    let mut escaped = false;
+    for (i, c) in text.char_indices().skip(1) {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' => escaped = true,
+            '[' => depth += 1,
+            ']' if depth == 0 => return Some(i),
+            ']' => depth -= 1,
+            _ => {}
+        }
+    }
+    None
+}
+
+// 解析自动链接 <scheme:...>，返回 (URL, 消耗的字节数)
+pub fn parse_autolink(text: &str) -> Option<(&str, usize)> {
+    let end = text.find('>')?;
+    let url = &text[1..end];
+    let scheme_len = url.find(':')?;
+    let scheme = &url[..scheme_len];
+    let valid_scheme = (2..=32).contains(&scheme.len())
+        && scheme.starts_with(|c: char| c.is_ascii_alphabetic())
+        && scheme.chars().all(|c| c.is_ascii_alphanumeric() || "+.-".contains(c));
+    if !valid_scheme || url.contains(|c: char| c.is_whitespace() || c == '<') {
+        return None;
+    }
+    Some((url, end + 1))
+}
+
+// 判断 text[pos..] 是否是裸 URL 的开头（需要位于单词边界）
+pub fn is_bare_url_start(text: &str, pos: usize) -> bool {
+    let rest = &text[pos..];
+    let at_boundary = text[..pos]
+        .chars()
+        .next_back()
+        .is_none_or(|c| c.is_whitespace() || "(*_~\"'".contains(c));
+    at_boundary && (rest.starts_with("https://") || rest.starts_with("http://") || rest.starts_with("www."))
+}
+
+// 解析裸 URL，返回 (显示文字, 实际链接, 消耗的字节数)
+pub fn parse_bare_url(text: &str) -> (&str, String, usize) {
+    let mut end = text
+        .find(|c: char| c.is_whitespace() || c == '<')
+        .unwrap_or(text.len());
+
+    // 去掉末尾的标点；不成对的右括号不属于 URL
+    loop {
+        let candidate = &text[..end];
+        match candidate.chars().next_back() {
+            Some(c) if "?!.,:*_~'\"".contains(c) => end -= 1,
+            Some(')') if candidate.matches(')').count() > candidate.matches('(').count() => end -= 1,
+            _ => break,
+        }
+    }
+
+    let shown = &text[..end];
+    let url = if shown.starts_with("www.") {
+        format!("http://{}", shown)
+    } else {
+        shown.to_string()
+    };
+    (shown, url, end)
+}
+
+// 链接引用标签规范化：忽略大小写，合并连续空白
+pub fn normalize_label(label: &str) -> String {
+    label.split_whitespace().collect::<Vec<_>>().join(" ").to_lowercase()
+}
+
+// 解析链接引用定义 [label]: url "title"，返回 (规范化标签, URL)
+pub fn parse_definition(line: &str) -> Option<(String, String)> {
+    let indent = line.len() - line.trim_start_matches(' ').len();
+    if indent > 3 {
+        return None;
+    }
+    let trimmed = line.trim();
+    let label_end = find_label_end(trimmed)?;
+    let label = &trimmed[1..label_end];
+    // [^1]: 是脚注定义，不是链接
+    if label.trim().is_empty() || label.starts_with('^') {
+        return None;
+    }
+    let rest = trimmed[label_end + 1..].strip_prefix(':')?;
+    let url = rest.split_whitespace().next()?;
+    let url = url.strip_prefix('<').and_then(|u| u.strip_suffix('>')).unwrap_or(url);
+    Some((normalize_label(label), escape::unescape(url)))
+}
+
+// 解析引用链接：[text][label]、[label][] 或 [label]
+// 返回 (链接文字, 引用标签, 消耗的字节数, 是否是显式引用)
+pub fn parse_reference_link(text: &str) -> Option<(&str, &str, usize, bool)> {
+    let label_end = find_label_end(text)?;
+    let label = &text[1..label_end];
+    let rest = &text[label_end + 1..];
+
+    if rest.starts_with('[') {
+        let ref_end = find_label_end(rest)?;
+        let reference = &rest[1..ref_end];
+        let reference = if reference.trim().is_empty() { label } else { reference };
+        return Some((label, reference, label_end + 1 + ref_end + 1, true));
+    }
+    if rest.starts_with('(') || rest.starts_with(':') || label.trim().is_empty() {
+        return None;
+    }
+    Some((label, label, label_end + 1, false))
+}
+
+// text[pos] 前面是否紧跟标识符字符：m[i][j]、arr[0] 这类下标表达式不是引用链接
+pub fn follows_identifier(text: &str, pos: usize) -> bool {
+    text[..pos].chars().next_back().is_some_and(|c| c.is_alphanumeric() || c == '_')
+}
+
+// 找出一行中所有显式引用链接的标签（跳过行内代码和下标表达式）
+pub fn explicit_references(line: &str) -> Vec<String> {
+    let mut references = Vec::new();
+    let mut in_code = false;
+    let mut pos = 0;
+    while pos < line.len() {
+        let c = line[pos..].chars().next().unwrap();
+        if c == '\\' && !in_code {
+            // 跳过被转义的字符，\[ 不是链接
+            pos += 1 + line[pos + 1..].chars().next().map_or(0, char::len_utf8);
+            continue;
+        }
+        if c == '`' {
+            in_code = !in_code;
+        } else if c == '[' && !in_code && !follows_identifier(line, pos)
+            && let Some((_, reference, consumed, true)) = parse_reference_link(&line[pos..])
+        {
+            references.push(normalize_label(reference));
+            pos += consumed;
+            continue;
+        }
+        pos += c.len_utf8();
+    }
+    references
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn explicit_reference_labels() {
+        assert_eq!(explicit_references("see [the docs][Docs] and [api][]"), vec!["docs", "api"]);
+        assert!(explicit_references("only [shortcut] here").is_empty());
+        assert!(explicit_references(r"escaped \[a][b] and `[c][d]`").is_empty());
+    }
+
+    #[test]
+    fn subscripts_are_not_references() {
+        assert!(explicit_references("Access m[i][j] here.").is_empty());
+        assert!(explicit_references("grid_2[x][y]").is_empty());
+        assert_eq!(explicit_references("(see [x][y])"), vec!["y"]);
+    }
+}
@@ -1,25 +1,56 @@
+use std::cell::RefCell;
+use std::collections::HashMap;
 use std::error::Error;
 use std::io::{Write, BufRead, BufReader, IsTerminal, Read};
 use std::env;
+use std::os::fd::AsRawFd;
+use std::path::Path;
+use std::rc::Rc;
 // use std::process::{Command, Stdio};
 // We only need Command and open from the blocking module.
 use pty_process::blocking::{open, Command as PtyCommand};
 
-// ANSI 颜色常量
+mod alert;
+mod diff;
+mod emphasis;
+mod fence;
+mod frame;
+mod highlight;
+mod escape;
+mod extract;
+mod inline;
+mod links;
+mod supsub;
+mod table;
+mod term;
+mod theme;
+mod width;
+mod wrap;
+
+use alert::AlertKind;
+use diff::DiffHighlighter;
+use emphasis::InlineToken;
+use extract::CodeBlock;
+use fence::Fence;
+use frame::{CodeFrame, FrameStyle};
+use highlight::Highlighter;
+use inline::Inline;
+use table::Table;
+use term::TermSize;
+use theme::Theme;
+
+// ANSI 颜色常量：Markdown 元素的样式由主题（theme.rs）决定，这里的常量用于程序自身的输出和与主题无关的固定样式
 const RESET: &str = "\x1b[0m";
 const BOLD: &str = "\x1b[1;33m";  // 黄色粗体，更明显
-const ITALIC: &str = "\x1b[3m";   // 斜体
-const BOLD_ITALIC: &str = "\x1b[1;3;33m";  // 粗斜体（粗体+斜体+黄色）
+const PLAIN_BOLD: &str = "\x1b[1m"; // 不改变颜色的粗体
 const CYAN: &str = "\x1b[36m";      // 标题
 const GREEN: &str = "\x1b[32m";     // 代码
 const YELLOW: &str = "\x1b[33m";    // 引用
-// const BLUE: &str = "\x1b[34m";      // 链接
-// const RED: &str = "\x1b[31m";       // 强调
-const MAGENTA: &str = "\x1b[35m";   // 列表项
-const LIGHT_GRAY_BG: &str = "\x1b[48;5;253m";  // 更淡的灰色背景
-const DARK_ORANGE_RED: &str = "\x1b[38;5;130m";  // 偏橘色的暗红色文字
 const GRAY: &str = "\x1b[90m";      // 灰色文字（用于水平分割线）
 
+// 等待 setext 下划线时最多保留的段落行数，更长的段落直接输出，不再可能成为标题
+const MAX_SETEXT_LINES: usize = 5;
+
 // 语言配置
 struct Language {
     title: &'static str,
@@ -33,6 +64,30 @@ struct Language {
     pipe_input: &'static str,
     explicit_cmd: &'static str,
     debug_mode: &'static str,
+    no_redraw: &'static str,
+    link_footnotes: &'static str,
+    extended: &'static str,
+    frame: &'static str,
+    line_numbers: &'static str,
+    truncate_code: &'static str,
+    no_chrome: &'static str,
+    save_code: &'static str,
+    print_code: &'static str,
+    width: &'static str,
+    max_width: &'static str,
+    center: &'static str,
+    theme: &'static str,
+    error_theme: &'static str,
+    code_saved: &'static str,
+    error_option_needs_value: &'static str,
+    error_no_code_block: &'static str,
+    code_block_start: &'static str,
+    code_block_end: &'static str,
+    alert_note: &'static str,
+    alert_tip: &'static str,
+    alert_important: &'static str,
+    alert_warning: &'static str,
+    alert_caution: &'static str,
     preparing: &'static str,
     started: &'static str,
     completed: &'static str,
@@ -64,6 +119,30 @@ const CHINESE: Language = Language {
     pipe_input: "管道输入",
     explicit_cmd: "显式命令",
     debug_mode: "调试模式",
+    no_redraw: "不原地重绘表格等待定内容，确定后再输出",
+    link_footnotes: "链接显示为 文字[n]，回答结束后列出所有 URL",
+    extended: "启用扩展语法：==高亮==、^上标^、~下标~",
+    frame: "代码块画出完整边框，语言显示在上边框中",
+    line_numbers: "带边框的代码块显示行号",
+    truncate_code: "带边框的代码块中截断超长的行，默认折行",
+    no_chrome: "不显示代码块的开始/结束行和边框",
+    save_code: "回答结束后把所有代码块保存到目录，按语言选择扩展名",
+    print_code: "回答结束后把第 N 个代码块原样输出到 stdout（渲染结果输出到 stderr）",
+    width: "按指定列数折行，默认使用终端宽度",
+    max_width: "正文最大宽度，分隔线和折行不超过该宽度",
+    center: "正文栏居中显示（默认最大宽度 80 列）",
+    theme: "配色主题：auto（按终端背景选择，默认）、dark、light 或 TOML 主题文件路径",
+    error_theme: "错误: 无法加载主题",
+    code_saved: "已保存 {} 个代码块到 ",
+    error_option_needs_value: "错误: {} 需要参数",
+    error_no_code_block: "错误: 回答中没有第 {} 个代码块",
+    code_block_start: "代码块开始",
+    code_block_end: "代码块结束",
+    alert_note: "说明",
+    alert_tip: "提示",
+    alert_important: "重要",
+    alert_warning: "警告",
+    alert_caution: "当心",
     preparing: "准备在 PTY 环境中启动",
     started: "进程已启动，开始流式 Markdown 渲染...",
     completed: "流式渲染完成！",
@@ -95,6 +174,30 @@ const ENGLISH: Language = Language {
     pipe_input: "Pipe input",
     explicit_cmd: "Explicit command",
     debug_mode: "Debug mode",
+    no_redraw: "Don't redraw pending blocks (tables) in place; print them once complete",
+    link_footnotes: "Show links as text[n] and list their URLs after the response",
+    extended: "Enable extended syntax: ==highlight==, ^superscript^, ~subscript~",
+    frame: "Draw code blocks in a full-width box with the language in the top border",
+    line_numbers: "Number lines in framed code blocks",
+    truncate_code: "Truncate over-long lines in framed code blocks instead of wrapping",
+    no_chrome: "Hide the start/end lines and borders of code blocks",
+    save_code: "Save every code block to a directory after the response, named by language",
+    print_code: "Print the raw content of code block N to stdout after the response (rendering goes to stderr)",
+    width: "Wrap text at N columns instead of the terminal width",
+    max_width: "Limit the reading width; rules and wrapping stay within N columns",
+    center: "Center the response in a reading column (80 columns unless --max-width is set)",
+    theme: "Color theme: auto (match the terminal background, default), dark, light, or a TOML theme file",
+    error_theme: "Error: cannot load theme",
+    code_saved: "Saved {} code blocks to ",
+    error_option_needs_value: "Error: {} requires a value",
+    error_no_code_block: "Error: the response has no code block {}",
+    code_block_start: "Code block",
+    code_block_end: "End of code block",
+    alert_note: "Note",
+    alert_tip: "Tip",
+    alert_important: "Important",
+    alert_warning: "Warning",
+    alert_caution: "Caution",
     preparing: "Preparing to start in PTY environment:",
     started: "process started, beginning streaming Markdown rendering...",
     completed: "Streaming rendering completed!",
@@ -115,42 +218,173 @@ const ENGLISH: Language = Language {
 };
 
 fn detect_language() -> &'static Language {
-    if let Ok(lang) = env::var("LANG") {
-        if lang.starts_with("zh") {
-            return &CHINESE;
-        }
+    if let Ok(lang) = env::var("LANG") && lang.starts_with("zh") {
+        return &CHINESE;
     }
-    if let Ok(lang) = env::var("LC_ALL") {
-        if lang.starts_with("zh") {
-            return &CHINESE;
-        }
+    if let Ok(lang) = env::var("LC_ALL") && lang.starts_with("zh") {
+        return &CHINESE;
     }
-    if let Ok(lang) = env::var("LC_MESSAGES") {
-        if lang.starts_with("zh") {
-            return &CHINESE;
-        }
+    if let Ok(lang) = env::var("LC_MESSAGES") && lang.starts_with("zh") {
+        return &CHINESE;
     }
     &ENGLISH
 }
 
+// 列表项标记类型
+enum ListMarker<'a> {
+    Bullet,
+    Ordered(&'a str, char), // (原始编号, 分隔符 . 或 ))
+}
+
+// 待定块（需要向后看才能确定的结构，如表格）的输出策略
+#[derive(Clone, Copy, PartialEq)]
+enum PendingPolicy {
+    Redraw, // 先输出临时渲染结果，块确定后原地擦除重绘（stdout 为终端时）
+    Hold,   // 缓冲待定块，确定后一次性输出（管道输出或调试模式）
+}
+
+// 代码块的开始/结束标记样式
+#[derive(Clone, Copy)]
+enum CodeChrome {
+    Lines,             // ┌─ 开始行和 └─ 结束行
+    Frame(FrameStyle), // 完整边框
+    Hidden,            // 不显示，只输出代码
+}
+
+// 链接目标：已知的 URL，或脚注模式下等待定义的引用标签
+#[derive(Clone, PartialEq)]
+enum LinkTarget {
+    Url(String),
+    Reference(String),
+}
+
+#[derive(Clone)]
 struct MarkdownRenderer {
-    in_code_block: bool,
+    code_fence: Option<Fence>, // 当前所在代码块的开始围栏
     in_list: bool,
+    list_content_indent: String,      // 当前列表项内容的缩进，用于续行对齐
+    paragraph_prefix: Option<String>, // 当前段落续行（懒惰续行）需要的前缀，空行时清除
+    quote_depth: usize,               // 当前引用的嵌套层数
+    code_quote_depth: usize,          // 代码块开始时所在的引用层数
+    alert: Option<AlertKind>,         // 当前最外层引用是 GitHub 提示块时的类型
     code_lang: String,
+    code_attributes: Vec<(String, String)>, // 信息字符串中语言之后的属性
+    highlighter: Option<Highlighter>, // 当前代码块语言的语法高亮器，保存跨行的词法状态
+    diff_highlighter: Option<DiffHighlighter>, // diff / patch 代码块
+    code_indent: usize,     // 列表项中的代码块：每行去掉的列表项内容缩进
+    code_prefix: String,    // 列表项中的代码块：输出时与列表项内容对齐的前缀
+    indented_code: bool,    // 是否在缩进代码块中
+    pending_blank_lines: usize, // 缩进代码块中暂缓输出的空行，代码块继续时才输出
+    code_chrome: CodeChrome,
+    frame: Option<CodeFrame>, // 当前代码块的边框
+    list_source_indent: usize,  // 当前列表项内容在原文中的列数
+    list_levels: Vec<usize>,    // 外层到当前各级列表项内容在原文中的列数，用于计算嵌套层级
+    candidate: Vec<String>,    // 等待确认的行：最后一行可能是表头，整个段落可能是 setext 标题
+    candidate_paragraph: bool, // candidate 是从头开始保留的段落，可以继续增长并成为 setext 标题
+    setext_level: Option<usize>,     // 下一行确认的 setext 标题级别，渲染候选行时使用
+    in_paragraph: bool,              // 上一行是否是段落文字
+    table: Option<Table>,
+    pending_policy: PendingPolicy,
+    provisional_rows: usize, // 已输出的临时内容占用的终端行数
+    term_size: TermSize,
+    width: Option<usize>, // 折行宽度：--width 指定的宽度或终端列数，输出不是终端时不折行
+    fixed_width: bool,    // 指定了 --width，终端尺寸变化时不更新折行宽度
+    max_width: Option<usize>, // 阅读宽度上限，超过时按上限折行
+    center: bool,             // 阅读栏居中，左侧留出空白
+    hyperlinks: bool, // 终端是否支持 OSC 8 超链接
+    link_definitions: Rc<HashMap<String, String>>, // 链接引用定义：规范化标签 -> URL，预览副本共享，新增定义时才复制
+    deferred_lines: Vec<String>,   // 含有未定义引用链接而推迟输出的行
+    unresolved_labels: Vec<String>, // 推迟超限后仍未定义的引用标签，结束时补充列出
+    link_footnotes: bool,           // 脚注模式：链接显示为 text[n]，URL 列表在最后输出
+    footnotes: RefCell<Vec<LinkTarget>>,
+    extended_inline: bool, // 扩展语法：==高亮==、^上标^、~下标~
+    unicode_glyphs: bool,  // 能否显示 ☐ ☑ 等符号，否则用 ASCII 代替
+    lang: &'static Language, // 代码块、提示块等输出标签的语言
+    code_blocks: Option<Vec<CodeBlock>>, // 需要提取代码块时收集所有围栏代码块
+    theme: Theme,
 }
 
 impl MarkdownRenderer {
-    fn new() -> Self {
+    // output_size 是渲染结果输出所在终端的尺寸，输出不是终端时为 None
+    fn new(pending_policy: PendingPolicy, output_size: Option<TermSize>, hyperlinks: bool, options: &CliOptions, lang: &'static Language, theme: Theme) -> Self {
+        let code_chrome = if options.no_chrome {
+            CodeChrome::Hidden
+        } else if options.framed_code {
+            CodeChrome::Frame(FrameStyle {
+                line_numbers: options.line_numbers,
+                truncate: options.truncate_code,
+            })
+        } else {
+            CodeChrome::Lines
+        };
         Self {
-            in_code_block: false,
+            code_fence: None,
             in_list: false,
+            list_content_indent: String::new(),
+            paragraph_prefix: None,
+            quote_depth: 0,
+            code_quote_depth: 0,
+            alert: None,
             code_lang: String::new(),
+            code_attributes: Vec::new(),
+            highlighter: None,
+            diff_highlighter: None,
+            code_indent: 0,
+            code_prefix: String::new(),
+            indented_code: false,
+            pending_blank_lines: 0,
+            code_chrome,
+            frame: None,
+            list_source_indent: 0,
+            list_levels: Vec::new(),
+            candidate: Vec::new(),
+            candidate_paragraph: false,
+            setext_level: None,
+            in_paragraph: false,
+            table: None,
+            pending_policy,
+            provisional_rows: 0,
+            term_size: output_size.unwrap_or(TermSize { cols: 80, rows: 24 }),
+            width: options.width.or(output_size.map(|size| size.cols)),
+            fixed_width: options.width.is_some(),
+            // 居中模式没有指定上限时使用 80 列
+            max_width: options.max_width.or(options.center.then_some(80)),
+            center: options.center,
+            hyperlinks,
+            link_definitions: Rc::new(HashMap::new()),
+            deferred_lines: Vec::new(),
+            unresolved_labels: Vec::new(),
+            link_footnotes: options.link_footnotes,
+            footnotes: RefCell::new(Vec::new()),
+            extended_inline: options.extended_inline,
+            unicode_glyphs: term::supports_unicode(),
+            lang,
+            code_blocks: (options.save_code.is_some() || options.print_code.is_some()).then(Vec::new),
+            theme,
+        }
+    }
+
+    // 终端尺寸变化后更新重绘和折行使用的宽度
+    fn resize(&mut self, size: TermSize) {
+        self.term_size = size;
+        if !self.fixed_width {
+            self.width = Some(size.cols);
         }
     }
 
     fn apply_inline_formatting(&self, text: &str) -> String {
-        // 完整的markdown格式支持：粗体、斜体、粗斜体、行内代码（星号和下划线）
-        let mut result = String::new();
+        self.apply_inline_formatting_with_style(text, "")
+    }
+
+    // base_style 是外层容器（标题、表头）的样式，内部片段结束后会恢复它
+    fn apply_inline_formatting_with_style(&self, text: &str, base_style: &str) -> String {
+        inline::render(&self.parse_inline(text), base_style, self.hyperlinks, &self.theme)
+    }
+
+    fn parse_inline(&self, text: &str) -> Vec<Inline> {
+        // 先把文本拆成片段：普通文字、已解析的节点（代码、链接等）和 * / _ 分隔符串，
+        // 再按 CommonMark 规则配对分隔符构建强调节点
+        let mut tokens = Vec::new();
         let mut pos = 0;
         
         while pos < text.len() {
@@ -159,86 +393,40 @@ impl MarkdownRenderer {
             
             if let Some((start, format_type, marker_len)) = candidates.into_iter().min_by_key(|&(pos, _, _)| pos) {
                 // 添加格式标记之前的文本
-                result.push_str(&text[pos..start]);
+                tokens.push(InlineToken::Text(text[pos..start].to_string()));
                 
                 match format_type.as_str() {
-                    "bold_italic_star" => {
-                        // ***text*** 粗斜体
-                        if let Some(end) = text[start + 3..].find("***") {
-                            let end = end + start + 3;
-                            let content = &text[start + 3..end];
-                            let formatted_content = self.apply_code_only_formatting(content);
-                            result.push_str(&format!("{}{}{}", BOLD_ITALIC, formatted_content, RESET));
-                            pos = end + 3;
-                        } else {
-                            result.push_str(&text[start..start + marker_len]);
-                            pos = start + marker_len;
-                        }
-                    }
-                    "bold_italic_underscore" => {
-                        // ___text___ 粗斜体
-                        if let Some(end) = text[start + 3..].find("___") {
-                            let end = end + start + 3;
-                            let content = &text[start + 3..end];
-                            let formatted_content = self.apply_code_only_formatting(content);
-                            result.push_str(&format!("{}{}{}", BOLD_ITALIC, formatted_content, RESET));
-                            pos = end + 3;
-                        } else {
-                            result.push_str(&text[start..start + marker_len]);
-                            pos = start + marker_len;
-                        }
-                    }
-                    "bold_star" => {
-                        // **text** 粗体
-                        if let Some(end) = text[start + 2..].find("**") {
-                            let end = end + start + 2;
-                            let content = &text[start + 2..end];
-                            let formatted_content = self.apply_nested_formatting(content);
-                            result.push_str(&format!("{}{}{}", BOLD, formatted_content, RESET));
-                            pos = end + 2;
-                        } else {
-                            result.push_str(&text[start..start + marker_len]);
-                            pos = start + marker_len;
-                        }
-                    }
-                    "bold_underscore" => {
-                        // __text__ 粗体
-                        if let Some(end) = text[start + 2..].find("__") {
-                            let end = end + start + 2;
-                            let content = &text[start + 2..end];
-                            let formatted_content = self.apply_nested_formatting(content);
-                            result.push_str(&format!("{}{}{}", BOLD, formatted_content, RESET));
-                            pos = end + 2;
-                        } else {
-                            result.push_str(&text[start..start + marker_len]);
-                            pos = start + marker_len;
+                    "escape" => {
+                        // \* 等反斜杠转义：输出标点本身，不再作为格式标记
+                        match text[start + 1..].chars().next().filter(|&c| escape::is_escapable(c)) {
+                            Some(c) => {
+                                tokens.push(InlineToken::Text(c.to_string()));
+                                pos = start + 1 + c.len_utf8();
+                            }
+                            None => {
+                                tokens.push(InlineToken::Text("\\".to_string()));
+                                pos = start + 1;
+                            }
                         }
                     }
-                    "italic_star" => {
-                        // *text* 斜体
-                        if let Some(end) = text[start + 1..].find('*') {
-                            let end = end + start + 1;
-                            let content = &text[start + 1..end];
-                            let formatted_content = self.apply_code_only_formatting(content);
-                            result.push_str(&format!("{}{}{}", ITALIC, formatted_content, RESET));
-                            pos = end + 1;
-                        } else {
-                            result.push_str(&text[start..start + marker_len]);
-                            pos = start + marker_len;
+                    "entity" => {
+                        // &amp; &#123; &#x2014; 等 HTML 实体
+                        match escape::decode_entity(&text[start..]) {
+                            Some((decoded, len)) => {
+                                tokens.push(InlineToken::Text(decoded));
+                                pos = start + len;
+                            }
+                            None => {
+                                tokens.push(InlineToken::Text("&".to_string()));
+                                pos = start + 1;
+                            }
                         }
                     }
-                    "italic_underscore" => {
-                        // _text_ 斜体
-                        if let Some(end) = text[start + 1..].find('_') {
-                            let end = end + start + 1;
-                            let content = &text[start + 1..end];
-                            let formatted_content = self.apply_code_only_formatting(content);
-                            result.push_str(&format!("{}{}{}", ITALIC, formatted_content, RESET));
-                            pos = end + 1;
-                        } else {
-                            result.push_str(&text[start..start + marker_len]);
-                            pos = start + marker_len;
-                        }
+                    "emphasis" => {
+                        // * 或 _ 分隔符串，能否开启/关闭强调由前后字符决定
+                        let (run, len) = emphasis::scan_delimiter_run(text, start);
+                        tokens.push(InlineToken::Delimiter(run));
+                        pos = start + len;
                     }
                     "code" => {
                         // `code` 行内代码
@@ -246,68 +434,218 @@ impl MarkdownRenderer {
                         let backtick_count = remaining.chars().take_while(|&c| c == '`').count();
                         
                         if backtick_count >= 3 {
-                            result.push_str(&text[start..start + backtick_count]);
+                            tokens.push(InlineToken::Text(text[start..start + backtick_count].to_string()));
                             pos = start + backtick_count;
                         } else if let Some(end) = self.find_matching_backticks(&text[start..], backtick_count) {
                             let end = end + start;
                             let content = &text[start + backtick_count..end];
-                            result.push_str(&format!("{}{} {} {}", LIGHT_GRAY_BG, DARK_ORANGE_RED, content, RESET));
+                            tokens.push(InlineToken::Node(Inline::Code(content.to_string())));
                             pos = end + backtick_count;
                         } else {
-                            result.push_str(&text[start..start + 1]);
+                            tokens.push(InlineToken::Text(text[start..start + backtick_count].to_string()));
+                            pos = start + backtick_count;
+                        }
+                    }
+                    "strike" | "highlight" => {
+                        // ~~text~~ 删除线，==text== 高亮：与 * _ 一样按左右侧规则配对，可以与其他强调嵌套
+                        let (run, len) = emphasis::scan_delimiter_run(text, start);
+                        tokens.push(InlineToken::Delimiter(run));
+                        pos = start + len;
+                    }
+                    "superscript" | "subscript" => {
+                        // ^sup^ 上标，~sub~ 下标，内容不能包含空白
+                        let marker = &text[start..start + 1];
+                        let content_end = text[start + 1..]
+                            .find(|c: char| c.is_whitespace() || marker.starts_with(c))
+                            .map(|end| end + start + 1)
+                            .filter(|&end| end > start + 1 && text[end..].starts_with(marker));
+                        if let Some(end) = content_end {
+                            let content = &text[start + 1..end];
+                            let converted = if format_type == "superscript" {
+                                supsub::to_superscript(content)
+                            } else {
+                                supsub::to_subscript(content)
+                            };
+                            // 无法用 Unicode 上下标表示时，保留标记并弱化显示
+                            match converted {
+                                Some(converted) => tokens.push(InlineToken::Text(converted)),
+                                None => tokens.push(InlineToken::Node(Inline::Dim(format!("{}{}", marker, content)))),
+                            }
+                            pos = end + 1;
+                        } else {
+                            tokens.push(InlineToken::Text(marker.to_string()));
+                            pos = start + 1;
+                        }
+                    }
+                    "link" => {
+                        // [text](url) 行内链接
+                        if let Some((label, url, consumed)) = links::parse_inline_link(&text[start..]) {
+                            let children = self.parse_inline(label);
+                            tokens.push(InlineToken::Node(self.link_node(children, &LinkTarget::Url(escape::unescape(url)))));
+                            pos = start + consumed;
+                        } else if let Some((label, reference, consumed, explicit)) = links::parse_reference_link(&text[start..])
+                            && let Some(target) = self.resolve_reference(reference, explicit && !links::follows_identifier(text, start))
+                        {
+                            // [text][label] / [label][] / [label] 引用链接
+                            let children = self.parse_inline(label);
+                            tokens.push(InlineToken::Node(self.link_node(children, &target)));
+                            pos = start + consumed;
+                        } else {
+                            tokens.push(InlineToken::Text(text[start..start + 1].to_string()));
+                            pos = start + 1;
+                        }
+                    }
+                    "autolink" => {
+                        // <https://...> 自动链接
+                        if let Some((url, consumed)) = links::parse_autolink(&text[start..]) {
+                            let label = url.strip_prefix("mailto:").unwrap_or(url);
+                            tokens.push(InlineToken::Node(inline::url_link(label, url)));
+                            pos = start + consumed;
+                        } else {
+                            tokens.push(InlineToken::Text(text[start..start + 1].to_string()));
                             pos = start + 1;
                         }
                     }
+                    "bare_url" => {
+                        // https://... 或 www.... 裸 URL
+                        let (shown, url, consumed) = links::parse_bare_url(&text[start..]);
+                        tokens.push(InlineToken::Node(inline::url_link(shown, &url)));
+                        pos = start + consumed;
+                    }
                     _ => {
-                        result.push_str(&text[start..start + 1]);
-                        pos = start + 1;
+                        tokens.push(InlineToken::Text(text[start..start + marker_len.max(1)].to_string()));
+                        pos = start + marker_len.max(1);
                     }
                 }
             } else {
                 // 没有更多格式标记，添加剩余文本
-                result.push_str(&text[pos..]);
+                tokens.push(InlineToken::Text(text[pos..].to_string()));
                 break;
             }
         }
         
-        result
+        emphasis::build(tokens)
     }
     
     fn find_format_candidates(&self, text: &str, offset: usize) -> Vec<(usize, String, usize)> {
         let mut candidates = Vec::new();
         
-        // 查找各种格式标记（按优先级：长的优先）
-        if let Some(pos) = text.find("***") {
-            candidates.push((pos + offset, "bold_italic_star".to_string(), 3));
+        // 查找各种格式标记；* 和 _ 统一作为分隔符串，配对在之后进行
+        if let Some(pos) = text.find('\\') {
+            candidates.push((pos + offset, "escape".to_string(), 1));
+        }
+        if let Some(pos) = text.find('&') {
+            candidates.push((pos + offset, "entity".to_string(), 1));
         }
-        if let Some(pos) = text.find("___") {
-            candidates.push((pos + offset, "bold_italic_underscore".to_string(), 3));
+        if let Some(pos) = text.find(['*', '_']) {
+            candidates.push((pos + offset, "emphasis".to_string(), 1));
         }
-        if let Some(pos) = text.find("**") {
-            candidates.push((pos + offset, "bold_star".to_string(), 2));
+        if let Some(pos) = text.find('`') {
+            candidates.push((pos + offset, "code".to_string(), 1));
         }
-        if let Some(pos) = text.find("__") {
-            candidates.push((pos + offset, "bold_underscore".to_string(), 2));
+        if let Some(pos) = text.find("~~") {
+            candidates.push((pos + offset, "strike".to_string(), 2));
         }
-        if let Some(pos) = text.find('*') {
-            // 确保这个*不是**或***的一部分
-            if !text[pos..].starts_with("**") {
-                candidates.push((pos + offset, "italic_star".to_string(), 1));
+        if self.extended_inline {
+            if let Some(pos) = text.find("==") {
+                candidates.push((pos + offset, "highlight".to_string(), 2));
             }
-        }
-        if let Some(pos) = text.find('_') {
-            // 确保这个_不是__或___的一部分
-            if !text[pos..].starts_with("__") {
-                candidates.push((pos + offset, "italic_underscore".to_string(), 1));
+            if let Some(pos) = text.find('^') {
+                candidates.push((pos + offset, "superscript".to_string(), 1));
+            }
+            // 单个 ~ 是下标，确保不是 ~~ 的一部分
+            if let Some(pos) = text.match_indices('~').map(|(i, _)| i).find(|&i| {
+                !text[i..].starts_with("~~") && !text[..i].ends_with('~')
+            }) {
+                candidates.push((pos + offset, "subscript".to_string(), 1));
             }
         }
-        if let Some(pos) = text.find('`') {
-            candidates.push((pos + offset, "code".to_string(), 1));
+        if let Some(pos) = text.find('[') {
+            candidates.push((pos + offset, "link".to_string(), 1));
+        }
+        if let Some(pos) = text.find('<') {
+            candidates.push((pos + offset, "autolink".to_string(), 1));
+        }
+        if let Some(pos) = text.char_indices().map(|(i, _)| i).find(|&i| links::is_bare_url_start(text, i)) {
+            candidates.push((pos + offset, "bare_url".to_string(), 0));
         }
         
         candidates
     }
     
+    // 查找引用链接的目标。脚注模式下尚未定义的显式引用先占一个编号（紧跟在标识符后的下标表达式除外）
+    fn resolve_reference(&self, reference: &str, explicit: bool) -> Option<LinkTarget> {
+        let label = links::normalize_label(reference);
+        match self.link_definitions.get(&label) {
+            Some(url) => Some(LinkTarget::Url(url.clone())),
+            None if self.link_footnotes && explicit => Some(LinkTarget::Reference(label)),
+            None => None,
+        }
+    }
+
+    // 带文字的链接节点；脚注模式下显示为 text[n]
+    // 自动链接和裸 URL 的文字就是 URL 本身，不经过这里
+    fn link_node(&self, children: Vec<Inline>, target: &LinkTarget) -> Inline {
+        let url = self.target_url(target);
+        let footnote = if self.link_footnotes {
+            let mut footnotes = self.footnotes.borrow_mut();
+            // 指向同一 URL 的链接共用一个编号
+            let number = match footnotes.iter().position(|t| t == target || url.is_some() && self.target_url(t) == url) {
+                Some(index) => index + 1,
+                None => {
+                    footnotes.push(target.clone());
+                    footnotes.len()
+                }
+            };
+            Some(number)
+        } else {
+            None
+        };
+        Inline::Link {
+            children,
+            url: url.cloned(),
+            footnote,
+        }
+    }
+
+    fn target_url<'a>(&'a self, target: &'a LinkTarget) -> Option<&'a String> {
+        match target {
+            LinkTarget::Url(url) => Some(url),
+            LinkTarget::Reference(label) => self.link_definitions.get(label),
+        }
+    }
+
+    // 结束时输出的链接列表：脚注模式下的编号 URL，以及推迟超限后才出现定义的引用
+    fn render_link_list(&self) -> String {
+        let mut entries = Vec::new();
+        for (i, target) in self.footnotes.borrow().iter().enumerate() {
+            let entry = match (self.target_url(target), target) {
+                (Some(url), _) => inline::render(&[inline::url_link(url, url)], "", self.hyperlinks, &self.theme),
+                (None, LinkTarget::Reference(label)) => format!("{}[{}]{}", GRAY, label, RESET),
+                (None, LinkTarget::Url(_)) => unreachable!(),
+            };
+            entries.push(format!("{}[{}]{} {}\n", GRAY, i + 1, RESET, entry));
+        }
+        for label in &self.unresolved_labels {
+            if let Some(url) = self.link_definitions.get(label) {
+                let entry = inline::render(&[inline::url_link(url, url)], "", self.hyperlinks, &self.theme);
+                entries.push(format!("{}[{}]{} {}\n", GRAY, label, RESET, entry));
+            }
+        }
+
+        if entries.is_empty() {
+            String::new()
+        } else {
+            format!("\n{}", entries.concat())
+        }
+    }
+
+    fn has_unresolved_references(&self, line: &str) -> bool {
+        links::explicit_references(line)
+            .iter()
+            .any(|label| !self.link_definitions.contains_key(label))
+    }
+
     fn find_matching_backticks(&self, text: &str, count: usize) -> Option<usize> {
         let search_text = &text[count..];
         let mut search_pos = 0;
@@ -327,84 +665,85 @@ impl MarkdownRenderer {
         None
     }
     
-    fn apply_nested_formatting(&self, text: &str) -> String {
-        // 在粗体内部，只处理斜体和行内代码
-        let mut result = String::new();
-        let mut pos = 0;
-        
-        while pos < text.len() {
-            let next_italic = text[pos..].find('*').map(|i| (i + pos, "italic"));
-            let next_code = text[pos..].find('`').map(|i| (i + pos, "code"));
-            
-            let next_format = match (next_italic, next_code) {
-                (Some(italic), Some(code)) => {
-                    if italic.0 < code.0 { Some(italic) } else { Some(code) }
-                }
-                (Some(italic), None) => Some(italic),
-                (None, Some(code)) => Some(code),
-                (None, None) => None,
-            };
-            
-            match next_format {
-                None => {
-                    result.push_str(&text[pos..]);
-                    break;
-                }
-                Some((start, "italic")) => {
-                    if let Some(end_offset) = text[start + 1..].find('*') {
-                        let end = end_offset + start + 1;
-                        let italic_content = &text[start + 1..end];
-                        
-                        result.push_str(&text[pos..start]);
-                        result.push_str(&format!("{}{}{}", ITALIC, italic_content, RESET));
-                        pos = end + 1;
-                    } else {
-                        result.push_str(&text[pos..start + 1]);
-                        pos = start + 1;
-                    }
-                }
-                Some((start, "code")) => {
-                    if let Some(end_offset) = text[start + 1..].find('`') {
-                        let end = end_offset + start + 1;
-                        let code_content = &text[start + 1..end];
-                        
-                        result.push_str(&text[pos..start]);
-                        result.push_str(&format!("{}{} {} {}", LIGHT_GRAY_BG, DARK_ORANGE_RED, code_content, RESET));
-                        pos = end + 1;
-                    } else {
-                        result.push_str(&text[pos..start + 1]);
-                        pos = start + 1;
-                    }
-                }
-                _ => pos += 1,
+    fn parse_list_marker<'a>(&self, trimmed: &'a str) -> Option<(ListMarker<'a>, &'a str)> {
+        // 无序列表：- 或 * 后跟空格，或单独的标记符
+        for bullet in ["-", "*"] {
+            if trimmed == bullet {
+                return Some((ListMarker::Bullet, ""));
+            }
+            if let Some(content) = trimmed.strip_prefix(bullet).and_then(|rest| rest.strip_prefix(' ')) {
+                return Some((ListMarker::Bullet, content));
             }
         }
-        
-        result
+
+        // 有序列表：1-9位数字，后跟 . 或 )，再跟空格或行尾
+        let digits = trimmed.chars().take_while(|c| c.is_ascii_digit()).count();
+        if digits == 0 || digits > 9 {
+            return None;
+        }
+        let delimiter = trimmed[digits..].chars().next()?;
+        if delimiter != '.' && delimiter != ')' {
+            return None;
+        }
+        let rest = &trimmed[digits + 1..];
+        if rest.is_empty() {
+            Some((ListMarker::Ordered(&trimmed[..digits], delimiter), ""))
+        } else {
+            rest.strip_prefix(' ')
+                .map(|content| (ListMarker::Ordered(&trimmed[..digits], delimiter), content))
+        }
     }
-    
-    fn apply_code_only_formatting(&self, text: &str) -> String {
-        // 在斜体内部，只处理行内代码
-        let mut result = text.to_string();
-        let mut pos = 0;
-        
-        while let Some(start) = result[pos..].find('`') {
-            let start = start + pos;
-            if let Some(end) = result[start + 1..].find('`') {
-                let end = end + start + 1;
-                let code_content = &result[start + 1..end];
-                let formatted = format!("{}{} {} {}", LIGHT_GRAY_BG, DARK_ORANGE_RED, code_content, RESET);
-                result.replace_range(start..end + 1, &formatted);
-                pos = start + formatted.len();
-            } else {
-                pos = start + 1;
-            }
+
+    // GFM 任务列表标记：[ ] 或 [x] 后跟空格或行尾，返回 (是否完成, 剩余内容)
+    fn parse_task_marker<'a>(&self, content: &'a str) -> Option<(bool, &'a str)> {
+        let done = match content.get(..3)? {
+            "[ ]" => false,
+            "[x]" | "[X]" => true,
+            _ => return None,
+        };
+        let rest = &content[3..];
+        if rest.is_empty() {
+            return Some((done, rest));
+        }
+        rest.strip_prefix(' ').map(|rest| (done, rest))
+    }
+
+    // ATX 标题：1-6 个 # 后跟空格或行尾，去掉结尾的 # 序列，返回 (级别, 标题文字)
+    fn parse_atx_heading<'a>(&self, trimmed: &'a str) -> Option<(usize, &'a str)> {
+        let level = trimmed.chars().take_while(|&c| c == '#').count();
+        if level == 0 || level > 6 {
+            return None;
+        }
+        let rest = &trimmed[level..];
+        if !rest.is_empty() && !rest.starts_with([' ', '\t']) {
+            return None;
+        }
+        let title = rest.trim();
+        // 结尾的 # 序列前必须有空格，否则属于标题文字（如 C#）
+        let without_closing = title.trim_end_matches('#');
+        if without_closing.is_empty() {
+            Some((level, ""))
+        } else if without_closing.ends_with([' ', '\t']) {
+            Some((level, without_closing.trim_end()))
+        } else {
+            Some((level, title))
         }
-        
-        result
     }
-    
 
+    // setext 标题的下划线：=== 为一级，--- 为二级
+    fn parse_setext_underline(&self, line: &str) -> Option<usize> {
+        let indent = line.len() - line.trim_start_matches(' ').len();
+        let trimmed = line.trim();
+        if indent > 3 || trimmed.is_empty() {
+            None
+        } else if trimmed.chars().all(|c| c == '=') {
+            Some(1)
+        } else if trimmed.chars().all(|c| c == '-') {
+            Some(2)
+        } else {
+            None
+        }
+    }
 
     fn is_horizontal_rule(&self, trimmed: &str) -> bool {
         // 检查是否为水平分割线
@@ -425,100 +764,643 @@ impl MarkdownRenderer {
         }
     }
 
+    // 记录链接引用定义，同一标签以第一次定义为准
+    fn define_link(&mut self, label: String, url: String) {
+        if !self.link_definitions.contains_key(&label) {
+            Rc::make_mut(&mut self.link_definitions).insert(label, url);
+        }
+    }
+
     fn render_line(&mut self, line: &str) -> String {
+        let committed = self.render_deferred_line(line);
+        self.with_provisional(committed)
+    }
+
+    // 含有尚未定义的引用链接（定义通常在回答末尾）时推迟输出，
+    // 直到所有引用都有了定义，或推迟的行数超过一屏
+    fn render_deferred_line(&mut self, line: &str) -> String {
+        // 脚注模式下引用在最后统一列出，不需要推迟
+        if self.link_footnotes {
+            return self.render_lookahead_line(line);
+        }
+        if self.deferred_lines.is_empty() && (self.code_fence.is_some() || !self.has_unresolved_references(line)) {
+            return self.render_lookahead_line(line);
+        }
+
+        if let Some((label, url)) = links::parse_definition(line) {
+            self.define_link(label, url);
+        }
+        self.deferred_lines.push(line.to_string());
+
+        let resolved = self.deferred_lines.iter().all(|l| !self.has_unresolved_references(l));
+        if resolved || self.deferred_lines.len() >= self.term_size.rows {
+            return self.replay_deferred();
+        }
+        String::new()
+    }
+
+    fn replay_deferred(&mut self) -> String {
+        let mut result = String::new();
+        for line in std::mem::take(&mut self.deferred_lines) {
+            // 仍未定义的引用按原文输出，记下标签以便定义在之后出现时补充列出
+            for label in links::explicit_references(&line) {
+                if !self.link_definitions.contains_key(&label) && !self.unresolved_labels.contains(&label) {
+                    self.unresolved_labels.push(label);
+                }
+            }
+            result.push_str(&self.render_lookahead_line(&line));
+        }
+        result
+    }
+
+    // 处理需要向后看的结构（目前是表格），返回已经确定的输出
+    fn render_lookahead_line(&mut self, line: &str) -> String {
         let trimmed = line.trim();
-        
-        // 处理代码块
-        if trimmed.starts_with("```") && trimmed.len() == 3 {
-            // 只有恰好3个反引号才作为代码块边界处理
-            if self.in_code_block {
-                self.in_code_block = false;
-                self.code_lang.clear();
-                return format!("{}{}└─ 代码块结束{}\n", 
-                    GREEN, BOLD, RESET);
+
+        // 处理表格（需要向后看一行才能确认表头）
+        if let Some(table) = &mut self.table {
+            if table::looks_like_row(trimmed) {
+                table.push_row(trimmed);
+                return String::new();
+            }
+            // 表格结束，输出整个表格后继续处理当前行
+            let mut result = self.flush_table();
+            result.push_str(&self.render_lookahead_line(line));
+            return result;
+        }
+
+        if let Some(header) = self.candidate.last() {
+            // 候选的最后一行下面是分隔行：表格，之前的行按段落输出
+            let header_cells = table::split_row(header.trim());
+            if let Some(alignments) = table::parse_delimiter_row(trimmed)
+                && alignments.len() == header_cells.len()
+            {
+                self.candidate.pop();
+                let result = self.flush_candidate();
+                self.table = Some(Table::new(header_cells, alignments));
+                self.paragraph_prefix = None;
+                self.in_paragraph = false;
+                return result;
+            }
+            // 段落下面跟着 === 或 ---：整个段落是 setext 标题，下划线本身不输出
+            if self.candidate_paragraph
+                && let Some(level) = self.parse_setext_underline(line)
+            {
+                let lines = std::mem::take(&mut self.candidate);
+                let title: Vec<&str> = lines.iter().map(|l| l.trim()).collect();
+                self.candidate_paragraph = false;
+                self.setext_level = Some(level);
+                return self.render_block_line(&title.join(" "));
+            }
+            // 段落还在继续，继续等待；段落过长（超过行数上限或一屏）时放弃 setext 标题，输出已保留的行
+            if self.candidate_paragraph && !trimmed.is_empty() && !self.is_block_start(trimmed) {
+                self.candidate.push(line.to_string());
+                let rows: usize = self.candidate.iter().map(|l| self.count_rows(&self.render_paragraph(l))).sum();
+                if self.candidate.len() > MAX_SETEXT_LINES || rows >= self.term_size.rows {
+                    return self.flush_candidate();
+                }
+                return String::new();
+            }
+            // 不是表格也不是标题，候选行按普通行输出
+            let mut result = self.flush_candidate();
+            result.push_str(&self.render_lookahead_line(line));
+            return result;
+        }
+
+        let table_row = self.code_fence.is_none() && !self.is_block_start(trimmed) && table::looks_like_row(trimmed);
+        if table_row || self.starts_paragraph(line) {
+            self.candidate_paragraph = self.starts_paragraph(line);
+            self.candidate.push(line.to_string());
+            return String::new();
+        }
+
+        self.render_block_line(line)
+    }
+
+    // 擦除上一次的临时输出，然后附加已确定的内容和待定块新的临时渲染
+    fn with_provisional(&mut self, committed: String) -> String {
+        let committed = self.with_margin(committed);
+        if self.pending_policy == PendingPolicy::Hold {
+            return committed;
+        }
+
+        let mut result = String::new();
+        if self.provisional_rows > 0 {
+            result.push_str(&format!("\r\x1b[{}A\x1b[J", self.provisional_rows));
+            self.provisional_rows = 0;
+        }
+        result.push_str(&committed);
+
+        // 临时内容超过一屏时无法整体原地擦除，只显示开头能擦除的部分，块确定后再输出完整内容
+        let preview = self.pending_preview();
+        let preview = self.fit_screen(self.with_margin(preview));
+        let rows = self.count_rows(&preview);
+        if rows > 0 {
+            result.push_str(&preview);
+            self.provisional_rows = rows;
+        }
+        result
+    }
+
+    // 待定块当前的临时渲染结果
+    fn pending_preview(&mut self) -> String {
+        if !self.deferred_lines.is_empty() {
+            // 推迟的行在副本上按当前已知的定义渲染；收集的代码块与预览无关，复制前先移出，
+            // 避免推迟期间每一行都复制整个回答
+            let code_blocks = self.code_blocks.take();
+            let mut scratch = self.clone();
+            self.code_blocks = code_blocks;
+            let mut preview = scratch.replay_deferred();
+            preview.push_str(&scratch.pending_preview());
+            return preview;
+        }
+        if let Some(table) = &self.table {
+            return table.render(&self.theme, |cell, style| self.apply_inline_formatting_with_style(cell, style));
+        }
+        self.candidate.iter().map(|line| self.render_paragraph(line)).collect()
+    }
+
+    // 计算一段输出在终端中占用的行数（考虑终端自动折行）
+    fn count_rows(&self, output: &str) -> usize {
+        output
+            .lines()
+            .map(|l| width::display_width(l).div_ceil(self.term_size.cols).max(1))
+            .sum()
+    }
+
+    // 截取输出开头在终端中占用不到一屏的整行
+    fn fit_screen(&self, output: String) -> String {
+        let mut rows = 0;
+        let mut fitted = String::new();
+        for line in output.split_inclusive('\n') {
+            rows += self.count_rows(line);
+            if rows >= self.term_size.rows {
+                break;
+            }
+            fitted.push_str(line);
+        }
+        fitted
+    }
+
+    fn flush_table(&mut self) -> String {
+        match self.table.take() {
+            Some(table) => table.render(&self.theme, |cell, style| self.apply_inline_formatting_with_style(cell, style)),
+            None => String::new(),
+        }
+    }
+
+    // 输入结束时调用，输出所有仍在等待的内容
+    fn finish(&mut self) -> String {
+        let mut result = self.replay_deferred();
+        result.push_str(&self.flush_candidate());
+        if self.indented_code {
+            let closing = self.close_indented_code();
+            result.push_str(&self.quote_gutter(closing));
+        }
+        result.push_str(&self.flush_table());
+        result.push_str(&self.render_link_list());
+        self.with_provisional(result)
+    }
+
+    // 确定不是表头或 setext 标题后，候选行按普通行输出
+    fn flush_candidate(&mut self) -> String {
+        self.candidate_paragraph = false;
+        let mut result = String::new();
+        for line in std::mem::take(&mut self.candidate) {
+            result.push_str(&self.render_block_line(&line));
+        }
+        result
+    }
+
+    // 当前行是否开始一个新的段落（从第一行开始保留的整个段落才可能成为 setext 标题）
+    fn starts_paragraph(&self, line: &str) -> bool {
+        let trimmed = line.trim();
+        // 段落续行、列表项内容和代码都不算
+        if self.code_fence.is_some()
+            || self.indented_code
+            || self.in_paragraph
+            || self.paragraph_prefix.is_some()
+            || self.in_list_item(line)
+            || self.leading_spaces(line) >= 4
+        {
+            return false;
+        }
+        !trimmed.is_empty()
+            && !self.is_block_start(trimmed)
+            && links::parse_definition(line).is_none()
+    }
+
+    // 是否是其他块结构的起始行（这些行不会被当作表头或段落续行）
+    fn is_block_start(&self, trimmed: &str) -> bool {
+        self.parse_atx_heading(trimmed).is_some()
+            || trimmed.starts_with('>')
+            || fence::parse_opening(trimmed).is_some()
+            || self.is_horizontal_rule(trimmed)
+            || self.parse_list_marker(trimmed).is_some()
+    }
+
+    // 去掉行首最多 max_depth 层引用标记 >（每个 > 前最多 3 个空格，后面可跟一个空格）
+    // 返回 (层数, 剩余内容)
+    fn strip_quote_markers<'a>(&self, line: &'a str, max_depth: usize) -> (usize, &'a str) {
+        let mut depth = 0;
+        let mut rest = line;
+        while depth < max_depth {
+            let indent = rest.len() - rest.trim_start_matches(' ').len();
+            match rest[indent..].strip_prefix('>') {
+                Some(after) if indent <= 3 => {
+                    rest = after.strip_prefix(' ').unwrap_or(after);
+                    depth += 1;
+                }
+                _ => break,
+            }
+        }
+        (depth, rest)
+    }
+
+    fn close_code_block(&mut self) -> String {
+        self.code_fence = None;
+        self.indented_code = false;
+        self.highlighter = None;
+        self.diff_highlighter = None;
+        self.code_lang.clear();
+        self.code_attributes.clear();
+        let prefix = std::mem::take(&mut self.code_prefix);
+        if let Some(frame) = self.frame.take() {
+            return format!("{}{}", prefix, frame.bottom());
+        }
+        match self.code_chrome {
+            // 代码行没有重置颜色，结束时重置
+            CodeChrome::Hidden => RESET.to_string(),
+            _ => format!("{}{}└─ {}{}\n", prefix, self.theme.code_chrome, self.lang.code_block_end, RESET),
+        }
+    }
+
+    // 代码块开始行；带边框时画出上边框，宽度扣除列表缩进和引用竖线
+    fn open_code_block(&mut self) -> String {
+        let attributes: Vec<String> = self
+            .code_attributes
+            .iter()
+            .map(|(key, value)| match value {
+                _ if value.is_empty() => key.clone(),
+                _ if value.contains(char::is_whitespace) => format!("{}=\"{}\"", key, value),
+                _ => format!("{}={}", key, value),
+            })
+            .collect();
+        let mut badge = self.code_lang.clone();
+        if !attributes.is_empty() {
+            let separator = if badge.is_empty() { "" } else { " " };
+            badge.push_str(&format!("{}{}{}", separator, GRAY, attributes.join(" ")));
+        }
+
+        match self.code_chrome {
+            CodeChrome::Hidden => String::new(),
+            CodeChrome::Frame(style) => {
+                let used = width::display_width(&self.code_prefix) + 2 * self.quote_depth;
+                let frame = CodeFrame::new(style, self.column_width().saturating_sub(used), &self.theme);
+                let top = frame.top(&badge);
+                self.frame = Some(frame);
+                format!("{}{}", self.code_prefix, top)
+            }
+            CodeChrome::Lines => {
+                let mut header = format!("{}{}┌─ {}", self.code_prefix, self.theme.code_chrome, self.lang.code_block_start);
+                if !badge.is_empty() {
+                    header.push_str(&format!(" {}", badge));
+                }
+                format!("{}{}\n", header, RESET)
+            }
+        }
+    }
+
+    // 代码块中的一行：diff 和支持的语言逐行高亮，其余语言保持绿色
+    fn render_code_line(&mut self, code: &str) -> String {
+        if let Some(frame) = &mut self.frame {
+            let code = frame::expand_tabs(code.trim_end_matches(['\r', '\n']));
+            let highlighted = match (&mut self.diff_highlighter, &mut self.highlighter) {
+                (Some(diff), _) => diff.highlight_line(&code),
+                (None, Some(highlighter)) => highlighter.highlight_line(&code),
+                (None, None) => format!("{}{}", self.theme.code, code),
+            };
+            let rows = frame.line(&highlighted);
+            return rows
+                .split_inclusive('\n')
+                .map(|row| format!("{}{}", self.code_prefix, row))
+                .collect();
+        }
+        if let Some(diff) = &mut self.diff_highlighter {
+            return format!("{}{}", self.code_prefix, diff.highlight_line(code));
+        }
+        match &mut self.highlighter {
+            Some(highlighter) => format!("{}{}", self.code_prefix, highlighter.highlight_line(code)),
+            None => format!("{}{}{}", self.code_prefix, self.theme.code, code),
+        }
+    }
+
+    // 结束缩进代码块，暂缓的空行放在代码块之后输出
+    fn close_indented_code(&mut self) -> String {
+        let mut result = self.close_code_block();
+        result.push_str(&"\n".repeat(std::mem::take(&mut self.pending_blank_lines)));
+        result
+    }
+
+    fn leading_spaces(&self, line: &str) -> usize {
+        line.len() - line.trim_start_matches(' ').len()
+    }
+
+    // 缩进的行属于当前列表项（续行、代码块、嵌套列表）
+    fn in_list_item(&self, line: &str) -> bool {
+        self.in_list && line.len() - line.trim_start().len() >= 2
+    }
+
+    // 开始代码块时记录所在的容器：列表项中的代码去掉内容缩进，输出时与内容对齐
+    fn enter_code_block(&mut self, in_item: bool, code_indent: usize) {
+        self.code_quote_depth = self.quote_depth;
+        self.paragraph_prefix = None;
+        if in_item {
+            self.code_indent = code_indent;
+            self.code_prefix = self.list_content_indent.clone();
+        } else {
+            // 没有缩进的代码块结束列表
+            self.code_indent = 0;
+            self.in_list = false;
+        }
+    }
+
+    // 渲染普通段落行，列表项中的续行带上对应的前缀
+    fn render_paragraph(&self, line: &str) -> String {
+        let prefix = match &self.paragraph_prefix {
+            Some(prefix) => Some(prefix),
+            None if self.in_list_item(line) => Some(&self.list_content_indent),
+            None => None,
+        };
+        match prefix {
+            Some(prefix) => self.wrap(format!("{}{}\n", prefix, self.apply_inline_formatting(line.trim())), prefix),
+            None => self.wrap(format!("{}\n", self.apply_inline_formatting(line.trim_end())), ""),
+        }
+    }
+
+    // 超过输出宽度的行在单词边界折行，续行以 indent 开头；引用竖线之后统一添加
+    fn wrap(&self, rendered: String, indent: &str) -> String {
+        match self.width {
+            Some(_) => wrap::wrap(&rendered, self.column_width().saturating_sub(2 * self.quote_depth), indent),
+            None => rendered,
+        }
+    }
+
+    // 正文栏的宽度：输出宽度（不是终端时按终端默认宽度），不超过阅读宽度上限
+    fn column_width(&self) -> usize {
+        let width = self.width.unwrap_or(self.term_size.cols);
+        self.max_width.map_or(width, |max| width.min(max))
+    }
+
+    // 居中模式下正文栏左侧的空白
+    fn margin(&self) -> String {
+        match self.width {
+            Some(width) if self.center => " ".repeat(width.saturating_sub(self.column_width()) / 2),
+            _ => String::new(),
+        }
+    }
+
+    // 给输出的每个非空行加上居中的左边距
+    fn with_margin(&self, output: String) -> String {
+        let margin = self.margin();
+        if margin.is_empty() {
+            return output;
+        }
+        output
+            .split_inclusive('\n')
+            .map(|line| {
+                if line.trim_end_matches(['\r', '\n']).is_empty() {
+                    line.to_string()
+                } else {
+                    format!("{}{}", margin, line)
+                }
+            })
+            .collect()
+    }
+
+    // 回答开始和结束时的分隔线，与正文栏同宽
+    fn banner(&self) -> String {
+        format!("{}{}", self.margin(), "═".repeat(self.column_width()))
+    }
+
+    // 处理引用容器：去掉 > 标记后按普通块渲染内容，每一层引用在左侧加一条竖线
+    fn render_block_line(&mut self, line: &str) -> String {
+        // 代码块内只识别代码块开始时所在的引用层，更深的 > 属于代码内容
+        let max_depth = if self.code_fence.is_some() { self.code_quote_depth } else { usize::MAX };
+        let (depth, content) = self.strip_quote_markers(line, max_depth);
+
+        let mut result = String::new();
+        let mut content = content;
+        if depth != self.quote_depth {
+            let trimmed = line.trim();
+            // 懒惰续行：缺少 > 的普通文字仍属于引用中的段落
+            let lazy = depth == 0
+                && self.in_paragraph
+                && self.code_fence.is_none()
+                && !trimmed.is_empty()
+                && !self.is_block_start(trimmed);
+            if lazy {
+                content = line;
             } else {
-                self.in_code_block = true;
-                return format!("{}{}┌─ 代码块开始{}\n", 
-                    GREEN, BOLD, RESET);
-            }
-        } else if trimmed.starts_with("```") && !self.in_code_block {
-            // 如果不在代码块内，且是```后跟语言标识，开始代码块
-            self.in_code_block = true;
-            if trimmed.len() > 3 {
-                self.code_lang = trimmed[3..].to_string();
-            }
-            return format!("{}{}┌─ 代码块开始 {}{}\n", 
-                GREEN, BOLD, &self.code_lang, RESET);
-        }
-
-        // 在代码块内部
-        if self.in_code_block {
-            return format!("{}{}", GREEN, line);
-        }
-
-        // 处理标题
-        if trimmed.starts_with('#') {
-            let level = trimmed.chars().take_while(|&c| c == '#').count();
-            let title = trimmed.trim_start_matches('#').trim();
-            let formatted_title = self.apply_inline_formatting(title);
-            let prefix = match level {
-                1 => format!("{}{}━━ ", CYAN, BOLD),
-                2 => format!("{}{}── ", CYAN, BOLD),
-                3 => format!("{}{}▸ ", CYAN, BOLD),
-                4 => format!("{}{}• ", CYAN, BOLD),
-                5 => format!("{}{}‣ ", CYAN, BOLD),
-                _ => format!("{}{}◦ ", CYAN, BOLD),
+                // 引用层数变化，结束其中的代码块和列表（在更新引用层数之前，结束行使用代码块所在层的竖线）
+                if (self.code_fence.is_some() || self.indented_code) && self.code_quote_depth != depth {
+                    let closing = self.close_indented_code();
+                    result.push_str(&self.quote_gutter(closing));
+                }
+                let starts_quote = self.quote_depth == 0 && depth > 0;
+                self.quote_depth = depth;
+                self.in_list = false;
+                self.paragraph_prefix = None;
+
+                // 引用的第一行是 [!NOTE] 等标记时作为提示块，未知类型按普通引用处理
+                self.alert = if starts_quote { AlertKind::parse(content.trim()) } else { self.alert.filter(|_| depth > 0) };
+                if starts_quote && let Some(kind) = self.alert {
+                    result.push_str(&self.quote_gutter(kind.title_line(self.lang, &self.theme, self.unicode_glyphs)));
+                    return result;
+                }
+            }
+        }
+
+        let rendered = self.render_block_content(content);
+        result.push_str(&self.quote_gutter(rendered));
+        result
+    }
+
+    // 给输出的每一行加上当前引用层数的竖线
+    fn quote_gutter(&self, rendered: String) -> String {
+        if self.quote_depth == 0 {
+            return rendered;
+        }
+        let level = format!("{}│{} ", self.theme.quote, RESET);
+        // 提示块的最外层竖线使用对应的颜色
+        let outer = match self.alert {
+            Some(kind) => format!("{}{}│{} ", PLAIN_BOLD, kind.color(&self.theme), RESET),
+            None => level.clone(),
+        };
+        let gutter = format!("{}{}", outer, level.repeat(self.quote_depth - 1));
+        rendered
+            .split_inclusive('\n')
+            .map(|line| format!("{}{}", gutter, line))
+            .collect()
+    }
+
+    fn render_block_content(&mut self, line: &str) -> String {
+        let trimmed = line.trim();
+        let after_paragraph = std::mem::replace(&mut self.in_paragraph, false);
+        
+        // 在代码块内部：遇到匹配的结束围栏才结束
+        if let Some(fence) = &self.code_fence {
+            let code_line = fence::strip_spaces(line, self.code_indent);
+            if fence.is_closed_by(code_line) {
+                return self.close_code_block();
+            }
+            let code = fence.strip_indent(code_line).to_string();
+            if let Some(block) = self.code_blocks.as_mut().and_then(|blocks| blocks.last_mut()) {
+                block.content.push_str(code.trim_end_matches(['\r', '\n']));
+                block.content.push('\n');
+            }
+            return self.render_code_line(&code);
+        }
+
+        // 列表项中的内容先去掉列表项的内容缩进，再判断是否是代码块
+        let in_item = self.in_list_item(line);
+        let item_indent = if in_item { self.leading_spaces(line).min(self.list_source_indent) } else { 0 };
+        let item_line = &line[item_indent..];
+
+        // 缩进代码块：不在段落中、缩进至少 4 个空格的行
+        if self.indented_code {
+            if trimmed.is_empty() {
+                self.pending_blank_lines += 1;
+                return String::new();
+            }
+            if self.leading_spaces(item_line) >= 4 {
+                let blank_lines: String = (0..std::mem::take(&mut self.pending_blank_lines))
+                    .map(|_| self.render_code_line("\n"))
+                    .collect();
+                return blank_lines + &self.render_code_line(&item_line[4..]);
+            }
+            let closing = self.close_indented_code();
+            return closing + &self.render_block_content(line);
+        }
+        if !after_paragraph && !trimmed.is_empty() && self.leading_spaces(item_line) >= 4 {
+            self.enter_code_block(in_item, item_indent);
+            self.indented_code = true;
+            return self.open_code_block() + &self.render_code_line(&item_line[4..]);
+        }
+
+        // 开始代码块，信息字符串拆分为语言和属性
+        if let Some((fence, info)) = fence::parse_opening(item_line) {
+            self.code_fence = Some(fence);
+            self.enter_code_block(in_item, item_indent);
+            self.code_lang = info.lang;
+            self.highlighter = Highlighter::new(&self.code_lang, &self.theme.syntax);
+            if matches!(self.code_lang.to_ascii_lowercase().as_str(), "diff" | "patch" | "udiff") {
+                self.diff_highlighter = Some(DiffHighlighter::new(&self.theme.syntax));
+            }
+            self.code_attributes = info.attributes;
+            if let Some(blocks) = &mut self.code_blocks {
+                blocks.push(CodeBlock { lang: self.code_lang.clone(), content: String::new() });
+            }
+            return self.open_code_block();
+        }
+
+        // 链接引用定义：记录下来，不输出
+        if let Some((label, url)) = links::parse_definition(line) {
+            self.define_link(label, url);
+            self.paragraph_prefix = None;
+            return String::new();
+        }
+
+        // 空行结束当前段落
+        if trimmed.is_empty() {
+            self.paragraph_prefix = None;
+            return "\n".to_string();
+        }
+
+        // 处理标题：ATX 标题，或下一行已确认的 setext 标题
+        let heading = match self.setext_level.take() {
+            Some(level) => Some((level, trimmed)),
+            None => self.parse_atx_heading(trimmed),
+        };
+        if let Some((level, title)) = heading {
+            self.paragraph_prefix = None;
+            let heading_style = self.theme.headings[level.clamp(1, 6) - 1].clone();
+            let formatted_title = self.apply_inline_formatting_with_style(title, &heading_style);
+            let marker = match level {
+                1 => "━━ ",
+                2 => "── ",
+                3 => "▸ ",
+                4 => "• ",
+                5 => "‣ ",
+                _ => "◦ ",
             };
-            return format!("{}{}{}{}\n", prefix, formatted_title, RESET, RESET);
+            let indent = " ".repeat(width::display_width(marker));
+            return self.wrap(format!("{}{}{}\n", heading_style, marker, formatted_title), &indent);
         }
 
         // 处理水平分割线
         if self.is_horizontal_rule(trimmed) {
-            return format!("{}{}{}\n", 
-                GRAY, "─".repeat(7), RESET);
+            self.paragraph_prefix = None;
+            let width = self.column_width().saturating_sub(2 * self.quote_depth);
+            return format!("{}{}{}\n", self.theme.rule, "─".repeat(width), RESET);
         }
 
         // 处理列表 - 必须在apply_inline_formatting之前检查
         // 改进列表检测：只有在空格后跟内容或单独的标记符才是列表
-        let is_list = trimmed.starts_with("- ") || 
-                     trimmed.starts_with("* ") || 
-                     (trimmed == "-") || 
-                     (trimmed == "*");
-        
-        if is_list {
-            // 计算缩进级别
-            let indent_level = (line.len() - line.trim_start().len()) / 4; // 每4个空格为一级
-            let indent = "  ".repeat(indent_level); // 每级2个空格缩进
+        if let Some((marker, content)) = self.parse_list_marker(trimmed) {
+            // 计算缩进级别：标记缩进到某个列表项的内容列才是它的子项
+            let leading = self.leading_spaces(line);
+            if !self.in_list {
+                self.list_levels.clear();
+            }
+            while self.list_levels.last().is_some_and(|&column| leading < column) {
+                self.list_levels.pop();
+            }
+            let indent = "  ".repeat(self.list_levels.len()); // 每级2个空格缩进
             
-            // 更智能地提取列表内容
-            let content = if trimmed.starts_with("- ") {
-                &trimmed[2..]
-            } else if trimmed.starts_with("* ") {
-                &trimmed[2..]
-            } else if trimmed == "-" || trimmed == "*" {
-                ""
-            } else {
-                trimmed
+            let task = self.parse_task_marker(content);
+            let formatted_content = match task {
+                // 已完成的任务弱化并加删除线
+                Some((true, rest)) => self.apply_inline_formatting_with_style(rest, &format!("{}{}", GRAY, self.theme.strikethrough)),
+                Some((false, rest)) => self.apply_inline_formatting(rest),
+                None => self.apply_inline_formatting(content),
+            };
+            let checkbox = task.map(|(done, _)| match (done, self.unicode_glyphs) {
+                (false, true) => "☐",
+                (true, true) => "☑",
+                (false, false) => "[ ]",
+                (true, false) => "[x]",
+            });
+            let marker_text = match (marker, checkbox) {
+                // 任务列表用复选框代替项目符号
+                (ListMarker::Bullet, Some(checkbox)) => format!(" {}", checkbox),
+                (ListMarker::Bullet, None) => " •".to_string(),
+                // 有序列表保留原始编号和分隔符，编号右对齐到至少2列的槽位
+                (ListMarker::Ordered(number, delimiter), Some(checkbox)) => format!("{:>2}{} {}", number, delimiter, checkbox),
+                (ListMarker::Ordered(number, delimiter), None) => format!("{:>2}{}", number, delimiter),
             };
-            let formatted_content = self.apply_inline_formatting(content);
+            // 续行与列表项内容对齐；记录原文中内容的列数（只有标记的空列表项算上标记后的空格）
             self.in_list = true;
-            return format!("{}{}{} •{} {}{}\n", 
-                indent, MAGENTA, BOLD, RESET, formatted_content, RESET);
+            self.list_source_indent = leading + trimmed.len() - content.len() + usize::from(content.is_empty());
+            self.list_levels.push(self.list_source_indent);
+            self.list_content_indent = format!("{}{} ", indent, " ".repeat(width::display_width(&marker_text)));
+            self.paragraph_prefix = Some(self.list_content_indent.clone());
+            self.in_paragraph = true;
+            let item = format!("{}{}{}{} {}{}\n", indent, self.theme.list_marker, marker_text, RESET, formatted_content, RESET);
+            return self.wrap(item, &self.list_content_indent);
         }
 
         // 处理普通文本中的格式
-        let mut result = line.to_string();
-        result = self.apply_inline_formatting(&result);
-
-        // 处理引用
-        if trimmed.starts_with('>') {
-            let content = trimmed[1..].trim();
-            let formatted_content = self.apply_inline_formatting(content);
-            return format!("{}{}│{} {}{}\n", 
-                YELLOW, BOLD, RESET, formatted_content, RESET);
+        if self.paragraph_prefix.is_none() && !self.in_list_item(line) {
+            // 没有缩进的普通段落结束列表
+            self.in_list = false;
         }
-
-        format!("{}\n", result)
+        let result = self.render_paragraph(line);
+        self.in_paragraph = true;
+        if self.in_list && self.paragraph_prefix.is_none() {
+            self.paragraph_prefix = Some(self.list_content_indent.clone());
+        }
+        result
     }
 }
 
@@ -534,11 +1416,24 @@ fn print_help() {
     println!();
     println!("{}:", lang.options);
     println!("  --debug           {}", lang.debug_mode);
+    println!("  --no-redraw       {}", lang.no_redraw);
+    println!("  --link-footnotes  {}", lang.link_footnotes);
+    println!("  --extended        {}", lang.extended);
+    println!("  --frame           {}", lang.frame);
+    println!("  --line-numbers    {}", lang.line_numbers);
+    println!("  --truncate-code   {}", lang.truncate_code);
+    println!("  --no-chrome       {}", lang.no_chrome);
+    println!("  --save-code <dir> {}", lang.save_code);
+    println!("  --print-code <N>  {}", lang.print_code);
+    println!("  --width <N>       {}", lang.width);
+    println!("  --max-width <N>   {}", lang.max_width);
+    println!("  --center          {}", lang.center);
+    println!("  --theme <name>    {}", lang.theme);
     println!("  --help            {}", lang.help);
     println!("  --                {}", lang.separator);
     println!();
     println!("{}:", lang.description);
-    if lang as *const _ == &CHINESE as *const _ {
+    if std::ptr::eq(lang, &CHINESE) {
         println!("  1. 无参数时：显示此帮助信息");
         println!("  2. 管道输入时：将输入作为提示词传递给默认的gemini程序");
         println!("  3. 显式命令时：在 -- 之后指定AI程序和它的所有参数");
@@ -552,7 +1447,7 @@ fn print_help() {
     println!();
     println!("{}:", lang.examples);
     println!("  {}                                               # {}", prog_name, lang.show_help);
-    if lang as *const _ == &CHINESE as *const _ {
+    if std::ptr::eq(lang, &CHINESE) {
         println!("  echo \"介绍Rust\" | {}                            # {}", prog_name, lang.pipe_input);
         println!("  {} -- gemini --model gemini-2.5-flash -p \"介绍Rust\"  # {}", prog_name, lang.explicit_cmd);
         println!("  {} -- claude --model sonnet -p \"解释async/await\"     # Claude", prog_name);
@@ -565,10 +1460,43 @@ fn print_help() {
     }
 }
 
-fn parse_args() -> (Option<String>, Option<Vec<String>>, bool) {
+// 命令行选项（不包括要运行的程序）
+struct CliOptions {
+    debug_mode: bool,
+    no_redraw: bool,
+    link_footnotes: bool,
+    extended_inline: bool,
+    framed_code: bool,
+    line_numbers: bool,
+    truncate_code: bool,
+    no_chrome: bool,
+    save_code: Option<String>, // 保存代码块的目录
+    print_code: Option<usize>, // 输出到 stdout 的代码块序号（从 1 开始）
+    width: Option<usize>,
+    max_width: Option<usize>,
+    center: bool,
+    theme: Option<String>, // 预设名称或主题文件路径
+}
+
+fn parse_args() -> (Option<String>, Option<Vec<String>>, CliOptions) {
     let lang = detect_language();
     let args: Vec<String> = std::env::args().collect();
-    let mut debug_mode = false;
+    let mut options = CliOptions {
+        debug_mode: false,
+        no_redraw: false,
+        link_footnotes: false,
+        extended_inline: false,
+        framed_code: false,
+        line_numbers: false,
+        truncate_code: false,
+        no_chrome: false,
+        save_code: None,
+        print_code: None,
+        width: None,
+        max_width: None,
+        center: false,
+        theme: None,
+    };
     let mut separator_found = false;
     let mut command = String::new();
     let mut command_args = Vec::new();
@@ -581,7 +1509,81 @@ fn parse_args() -> (Option<String>, Option<Vec<String>>, bool) {
                 std::process::exit(0);
             }
             "--debug" => {
-                debug_mode = true;
+                options.debug_mode = true;
+            }
+            "--no-redraw" => {
+                options.no_redraw = true;
+            }
+            "--link-footnotes" => {
+                options.link_footnotes = true;
+            }
+            "--extended" => {
+                options.extended_inline = true;
+            }
+            "--frame" => {
+                options.framed_code = true;
+            }
+            "--line-numbers" => {
+                options.line_numbers = true;
+            }
+            "--truncate-code" => {
+                options.truncate_code = true;
+            }
+            "--no-chrome" => {
+                options.no_chrome = true;
+            }
+            "--save-code" => {
+                i += 1;
+                match args.get(i) {
+                    Some(dir) => options.save_code = Some(dir.clone()),
+                    None => {
+                        eprintln!("{}", lang.error_option_needs_value.replace("{}", "--save-code"));
+                        std::process::exit(1);
+                    }
+                }
+            }
+            "--print-code" => {
+                i += 1;
+                match args.get(i).and_then(|n| n.parse::<usize>().ok()).filter(|&n| n > 0) {
+                    Some(n) => options.print_code = Some(n),
+                    None => {
+                        eprintln!("{}", lang.error_option_needs_value.replace("{}", "--print-code"));
+                        std::process::exit(1);
+                    }
+                }
+            }
+            "--width" => {
+                i += 1;
+                match args.get(i).and_then(|n| n.parse::<usize>().ok()).filter(|&n| n > 0) {
+                    Some(n) => options.width = Some(n),
+                    None => {
+                        eprintln!("{}", lang.error_option_needs_value.replace("{}", "--width"));
+                        std::process::exit(1);
+                    }
+                }
+            }
+            "--max-width" => {
+                i += 1;
+                match args.get(i).and_then(|n| n.parse::<usize>().ok()).filter(|&n| n > 0) {
+                    Some(n) => options.max_width = Some(n),
+                    None => {
+                        eprintln!("{}", lang.error_option_needs_value.replace("{}", "--max-width"));
+                        std::process::exit(1);
+                    }
+                }
+            }
+            "--center" => {
+                options.center = true;
+            }
+            "--theme" => {
+                i += 1;
+                match args.get(i) {
+                    Some(theme) => options.theme = Some(theme.clone()),
+                    None => {
+                        eprintln!("{}", lang.error_option_needs_value.replace("{}", "--theme"));
+                        std::process::exit(1);
+                    }
+                }
             }
             "--" => {
                 separator_found = true;
@@ -606,9 +1608,9 @@ fn parse_args() -> (Option<String>, Option<Vec<String>>, bool) {
     
     // 如果没有找到分隔符，返回 None 表示需要特殊处理
     if !separator_found {
-        (None, None, debug_mode)
+        (None, None, options)
     } else {
-        (Some(command), Some(command_args), debug_mode)
+        (Some(command), Some(command_args), options)
     }
 }
 
@@ -618,7 +1620,8 @@ fn main() -> Result<(), Box<dyn Error>> {
     let lang = detect_language();
     
     // --- 解析命令行参数 ---
-    let (command_opt, command_args_opt, debug_mode) = parse_args();
+    let (command_opt, command_args_opt, options) = parse_args();
+    let debug_mode = options.debug_mode;
     
     // 检查是否有管道输入或显式命令
     let (command, command_args) = match (command_opt, command_args_opt) {
@@ -655,71 +1658,137 @@ fn main() -> Result<(), Box<dyn Error>> {
         _ => unreachable!(),
     };
     
+    // 输出代码块到 stdout 时，渲染结果改为输出到 stderr，stdout 只包含代码
+    let print_to_stderr = options.print_code.is_some();
+    let mut out: Box<dyn Write> = if print_to_stderr {
+        Box::new(std::io::stderr())
+    } else {
+        Box::new(std::io::stdout())
+    };
+    // 尺寸、超链接支持和重绘都以实际输出渲染结果的终端为准
+    let output_fd = if print_to_stderr { libc::STDERR_FILENO } else { libc::STDOUT_FILENO };
+    let output_is_terminal = unsafe { libc::isatty(output_fd) } == 1;
+
+    // 只有需要时才查询终端背景色（查询可能要等待终端响应）
+    let theme_name = options.theme.as_deref().unwrap_or("auto");
+    let light_background = !matches!(theme_name, "dark" | "light")
+        && term::light_background(output_is_terminal).unwrap_or(false);
+    let theme = match Theme::load(theme_name, light_background) {
+        Ok(theme) => theme,
+        Err(e) => {
+            eprintln!("{} {}", lang.error_theme, e);
+            std::process::exit(1);
+        }
+    };
+
     if debug_mode {
-        println!("{}{}{}{}{}", CYAN, BOLD, lang.debug_enabled, RESET, RESET);
-        println!("{}{}{} {}{}{}", CYAN, BOLD, lang.command, command, RESET, RESET);
-        println!("{}{}{} {:?}{}{}", CYAN, BOLD, lang.args, command_args, RESET, RESET);
+        writeln!(out, "{}{}{}{}{}", CYAN, BOLD, lang.debug_enabled, RESET, RESET)?;
+        writeln!(out, "{}{}{} {}{}{}", CYAN, BOLD, lang.command, command, RESET, RESET)?;
+        writeln!(out, "{}{}{} {:?}{}{}", CYAN, BOLD, lang.args, command_args, RESET, RESET)?;
     }
     
     // --- 第一部分：创建 PTY 并准备 AI 命令 ---
     
     let (pty_master_reader, pts) = open()?;
+    // 子进程按 aimd 所在终端的尺寸排版输出，之后终端尺寸变化时同步给子进程
+    if let Some(size) = term::controlling_size() {
+        pty_master_reader.resize(pty_process::Size::new(size.rows as u16, size.cols as u16))?;
+    }
+    term::forward_resize(pty_master_reader.as_raw_fd());
 
-    println!("{} {}...", lang.preparing, command);
+    writeln!(out, "{} {}...", lang.preparing, command)?;
     
     let mut ai_child = PtyCommand::new(&command)
         .args(&command_args)
         .spawn(pts)?;
     
-    println!("{}{}{} {}{}{}", CYAN, BOLD, command, lang.started, RESET, RESET);
-    println!("{}", "═".repeat(60));
+    writeln!(out, "{}{}{} {}{}{}", CYAN, BOLD, command, lang.started, RESET, RESET)?;
 
     // --- 流式 Markdown 渲染 ---
     let mut reader = BufReader::new(pty_master_reader);
     let mut line = String::new();
-    let mut renderer = MarkdownRenderer::new();
+    // 调试输出会打断原地重绘，此时和管道输出一样缓冲待定块
+    let pending_policy = if output_is_terminal && !debug_mode && !options.no_redraw {
+        PendingPolicy::Redraw
+    } else {
+        PendingPolicy::Hold
+    };
+    let mut renderer = MarkdownRenderer::new(pending_policy, term::size_of(output_fd), term::supports_hyperlinks(output_fd), &options, lang, theme);
+    writeln!(out, "{}", renderer.banner())?;
 
     loop {
         line.clear();
         if debug_mode {
-            println!("{}{}{}{}{}", CYAN, BOLD, lang.reading_next_line, RESET, RESET);
+            writeln!(out, "{}{}{}{}{}", CYAN, BOLD, lang.reading_next_line, RESET, RESET)?;
         }
-        let bytes_read = reader.read_line(&mut line)?;
+        let bytes_read = match reader.read_line(&mut line) {
+            Ok(n) => n,
+            // 子进程退出后 Linux 上读取 PTY 会返回 EIO，视为输入结束
+            Err(e) if e.raw_os_error() == Some(libc::EIO) => 0,
+            Err(e) => return Err(e.into()),
+        };
         if debug_mode {
-            println!("{}{}{} {}: {:?}{}{}", CYAN, BOLD, lang.bytes_read.replace("{}", &bytes_read.to_string()), bytes_read, line.trim(), RESET, RESET);
+            writeln!(out, "{}{}{} {}: {:?}{}{}", CYAN, BOLD, lang.bytes_read.replace("{}", &bytes_read.to_string()), bytes_read, line.trim(), RESET, RESET)?;
         }
 
         if bytes_read == 0 {
             if debug_mode {
-                println!("{}{}{}{}{}", CYAN, BOLD, lang.read_finished, RESET, RESET);
+                writeln!(out, "{}{}{}{}{}", CYAN, BOLD, lang.read_finished, RESET, RESET)?;
             }
+            write!(out, "{}", renderer.finish())?;
+            out.flush()?;
             break;
         }
 
         // 在debug模式下显示原始内容
         if debug_mode {
-            print!("{}{}[raw]{} {}", YELLOW, BOLD, RESET, line);
-            std::io::stdout().flush()?;
+            write!(out, "{}{}[raw]{} {}", YELLOW, BOLD, RESET, line)?;
+            out.flush()?;
         }
 
         // 立即渲染并输出当前行
         if debug_mode {
-            println!("{}{}{}{}{}", CYAN, BOLD, lang.starting_render, RESET, RESET);
+            writeln!(out, "{}{}{}{}{}", CYAN, BOLD, lang.starting_render, RESET, RESET)?;
+        }
+        if term::take_resized() && let Some(size) = term::size_of(output_fd) {
+            renderer.resize(size);
         }
         let rendered = renderer.render_line(&line);
         if debug_mode {
-            println!("{}{}{} {}{}{}", CYAN, BOLD, lang.render_completed, rendered.len(), RESET, RESET);
+            writeln!(out, "{}{}{} {}{}{}", CYAN, BOLD, lang.render_completed, rendered.len(), RESET, RESET)?;
         }
-        print!("{}", rendered);
-        std::io::stdout().flush()?;
+        write!(out, "{}", rendered)?;
+        out.flush()?;
     }
 
-    println!("\n{}", "═".repeat(60));
-    println!("{}{}{}{}{}", GREEN, BOLD, lang.completed, RESET, RESET);
+    writeln!(out, "\n{}", renderer.banner())?;
+    writeln!(out, "{}{}{}{}{}", GREEN, BOLD, lang.completed, RESET, RESET)?;
 
     // --- 等待 AI 进程结束 ---
     let ai_status = ai_child.wait()?;
-    println!("{} {}: {:?}", command, lang.exited, ai_status);
+    writeln!(out, "{} {}: {:?}", command, lang.exited, ai_status)?;
+
+    // --- 提取代码块 ---
+    let blocks = renderer.code_blocks.unwrap_or_default();
+    if let Some(dir) = &options.save_code {
+        let paths = extract::save_all(&blocks, Path::new(dir))?;
+        writeln!(out, "{}{}{}{}", GREEN, lang.code_saved.replace("{}", &paths.len().to_string()), dir, RESET)?;
+        for path in paths {
+            writeln!(out, "  {}", path.display())?;
+        }
+    }
+    if let Some(n) = options.print_code {
+        match blocks.get(n - 1) {
+            Some(block) => {
+                out.flush()?;
+                std::io::stdout().write_all(block.content.as_bytes())?;
+            }
+            None => {
+                eprintln!("{}", lang.error_no_code_block.replace("{}", &n.to_string()));
+                std::process::exit(1);
+            }
+        }
+    }
 
     Ok(())
 }
@@ -0,0 +1,178 @@
+// GFM 表格支持：识别表头/分隔行，按列对齐并用制表符绘制边框
+use crate::width::display_width;
+use crate::theme::Theme;
+use crate::RESET;
+
+#[derive(Clone, Copy, PartialEq)]
+pub enum Alignment {
+    Left,
+    Center,
+    Right,
+}
+
+#[derive(Clone)]
+pub struct Table {
+    header: Vec<String>,
+    alignments: Vec<Alignment>,
+    rows: Vec<Vec<String>>,
+}
+
+// 粗略判断一行是否可能是表格行（包含未转义的 |）
+pub fn looks_like_row(trimmed: &str) -> bool {
+    trimmed.starts_with('|') || split_row(trimmed).len() > 1
+}
+
+// 拆分表格行为单元格：去掉首尾的 |，按未转义的 | 分割，\| 还原为 |
+pub fn split_row(trimmed: &str) -> Vec<String> {
+    let mut cells = Vec::new();
+    let mut current = String::new();
+    let mut chars = trimmed.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' if chars.peek() == Some(&'|') => {
+                current.push('|');
+                chars.next();
+            }
+            '|' => {
+                cells.push(current.trim().to_string());
+                current.clear();
+            }
+            _ => current.push(c),
+        }
+    }
+    cells.push(current.trim().to_string());
+
+    // 开头或结尾的 | 会产生空单元格，去掉
+    if trimmed.starts_with('|') && !cells.is_empty() {
+        cells.remove(0);
+    }
+    if trimmed.len() > 1 && trimmed.ends_with('|') && !trimmed.ends_with("\\|") && !cells.is_empty() {
+        cells.pop();
+    }
+    cells
+}
+
+// 解析分隔行（如 |:---|:---:|---:|），返回每列的对齐方式
+pub fn parse_delimiter_row(trimmed: &str) -> Option<Vec<Alignment>> {
+    if !trimmed.contains('-') || !trimmed.contains('|') {
+        return None;
+    }
+    let cells = split_row(trimmed);
+    if cells.is_empty() {
+        return None;
+    }
+
+    let mut alignments = Vec::new();
+    for cell in cells {
+        let left = cell.starts_with(':');
+        let right = cell.ends_with(':') && cell.len() > 1;
+        let dashes = cell.trim_start_matches(':').trim_end_matches(':');
+        if dashes.is_empty() || !dashes.chars().all(|c| c == '-') {
+            return None;
+        }
+        alignments.push(match (left, right) {
+            (true, true) => Alignment::Center,
+            (false, true) => Alignment::Right,
+            _ => Alignment::Left,
+        });
+    }
+    Some(alignments)
+}
+
+impl Table {
+    pub fn new(header: Vec<String>, alignments: Vec<Alignment>) -> Self {
+        Self {
+            header,
+            alignments,
+            rows: Vec::new(),
+        }
+    }
+
+    pub fn column_count(&self) -> usize {
+        self.alignments.len()
+    }
+
+    pub fn push_row(&mut self, trimmed: &str) {
+        // 单元格数量以表头为准：多余的丢弃，不足的补空
+        let mut cells = split_row(trimmed);
+        cells.resize(self.column_count(), String::new());
+        self.rows.push(cells);
+    }
+
+    // 渲染整个表格，format_cell(内容, 外层样式) 用于对单元格内容应用行内格式
+    pub fn render(&self, theme: &Theme, format_cell: impl Fn(&str, &str) -> String) -> String {
+        let header: Vec<String> = self.header.iter().map(|c| format_cell(c, &theme.table_header)).collect();
+        let rows: Vec<Vec<String>> = self
+            .rows
+            .iter()
+            .map(|row| row.iter().map(|c| format_cell(c, "")).collect())
+            .collect();
+
+        // 按显示宽度计算每列宽度（兼容 CJK 和 ANSI 转义）
+        let mut widths: Vec<usize> = header.iter().map(|c| display_width(c)).collect();
+        for row in &rows {
+            for (i, cell) in row.iter().enumerate() {
+                widths[i] = widths[i].max(display_width(cell));
+            }
+        }
+
+        let mut result = String::new();
+        let border = &theme.table_border;
+        result.push_str(&self.border_line(&widths, border, '┌', '┬', '┐'));
+        result.push_str(&self.content_line(&header, &widths, border));
+        result.push_str(&self.border_line(&widths, border, '├', '┼', '┤'));
+        for row in &rows {
+            result.push_str(&self.content_line(row, &widths, border));
+        }
+        result.push_str(&self.border_line(&widths, border, '└', '┴', '┘'));
+        result
+    }
+
+    fn border_line(&self, widths: &[usize], border: &str, left: char, middle: char, right: char) -> String {
+        let segments: Vec<String> = widths.iter().map(|&w| "─".repeat(w + 2)).collect();
+        format!("{}{}{}{}{}\n", border, left, segments.join(&middle.to_string()), right, RESET)
+    }
+
+    fn content_line(&self, cells: &[String], widths: &[usize], border: &str) -> String {
+        let mut line = format!("{}│{}", border, RESET);
+        for (i, cell) in cells.iter().enumerate() {
+            let padding = widths[i] - display_width(cell);
+            let (left_pad, right_pad) = match self.alignments[i] {
+                Alignment::Left => (0, padding),
+                Alignment::Center => (padding / 2, padding - padding / 2),
+                Alignment::Right => (padding, 0),
+            };
+            line.push_str(&format!(" {}{}{} {}│{}",
+                " ".repeat(left_pad), cell, " ".repeat(right_pad), border, RESET));
+        }
+        line.push('\n');
+        line
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delimiter_row() {
+        use Alignment::*;
+        assert!(parse_delimiter_row("|:---|:---:|---:|") == Some(vec![Left, Center, Right]));
+        assert!(parse_delimiter_row("--- | :-:") == Some(vec![Left, Center]));
+        assert!(parse_delimiter_row("| - |") == Some(vec![Left]));
+        // 没有 | 的是 setext 下划线或分隔线，不是表格
+        assert!(parse_delimiter_row("---").is_none());
+        assert!(parse_delimiter_row("| a | b |").is_none());
+        assert!(parse_delimiter_row("|:-: x|").is_none());
+        assert!(parse_delimiter_row("| : |").is_none());
+    }
+
+    #[test]
+    fn split_cells() {
+        assert_eq!(split_row("| a | b |"), vec!["a", "b"]);
+        assert_eq!(split_row("a | b"), vec!["a", "b"]);
+        assert_eq!(split_row(r"| a \| b | c |"), vec!["a | b", "c"]);
+        assert_eq!(split_row("| | x |"), vec!["", "x"]);
+    }
+}
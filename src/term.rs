@@ -0,0 +1,220 @@
+// 终端相关的辅助函数
+use std::fs::OpenOptions;
+use std::io::{Read, Write};
+use std::os::fd::{AsRawFd, RawFd};
+use std::os::unix::fs::OpenOptionsExt;
+use std::sync::atomic::{AtomicBool, AtomicI32, Ordering};
+
+// SIGWINCH 处理函数使用的状态：终端尺寸是否变化过，以及需要同步尺寸的 PTY
+static RESIZED: AtomicBool = AtomicBool::new(false);
+static PTY_FD: AtomicI32 = AtomicI32::new(-1);
+
+// 终端尺寸（列数, 行数）
+#[derive(Clone, Copy)]
+pub struct TermSize {
+    pub cols: usize,
+    pub rows: usize,
+}
+
+// 读取 fd（渲染结果的输出，stdout 或 --print-code 时的 stderr）所在终端的尺寸，不是终端或读取失败时返回 None
+pub fn size_of(fd: RawFd) -> Option<TermSize> {
+    let ws = window_size(fd)?;
+    Some(TermSize {
+        cols: ws.ws_col as usize,
+        rows: ws.ws_row as usize,
+    })
+}
+
+// aimd 所在终端的尺寸：stdout 可能被重定向（如 --print-code），依次尝试 stdout、stderr、stdin
+pub fn controlling_size() -> Option<TermSize> {
+    let ws = [libc::STDOUT_FILENO, libc::STDERR_FILENO, libc::STDIN_FILENO]
+        .into_iter()
+        .find_map(window_size)?;
+    Some(TermSize {
+        cols: ws.ws_col as usize,
+        rows: ws.ws_row as usize,
+    })
+}
+
+fn window_size(fd: RawFd) -> Option<libc::winsize> {
+    let mut ws: libc::winsize = unsafe { std::mem::zeroed() };
+    let ret = unsafe { libc::ioctl(fd, libc::TIOCGWINSZ, &mut ws) };
+    (ret == 0 && ws.ws_col != 0).then_some(ws)
+}
+
+// 终端尺寸变化时把新尺寸同步给子进程的 PTY；内核随后会向子进程发送 SIGWINCH
+pub fn forward_resize(pty_fd: RawFd) {
+    PTY_FD.store(pty_fd, Ordering::Relaxed);
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = on_sigwinch as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        // 被信号打断的读取自动重试
+        action.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut action.sa_mask);
+        libc::sigaction(libc::SIGWINCH, &action, std::ptr::null_mut());
+    }
+}
+
+// 信号处理函数中只调用 ioctl 和原子操作；ioctl 会改写 errno，
+// 返回前恢复，以免主循环在信号打断的系统调用之后读到错误的 errno
+extern "C" fn on_sigwinch(_: libc::c_int) {
+    let errno = unsafe { *errno_location() };
+    RESIZED.store(true, Ordering::Relaxed);
+    let pty_fd = PTY_FD.load(Ordering::Relaxed);
+    let ws = [libc::STDOUT_FILENO, libc::STDERR_FILENO, libc::STDIN_FILENO]
+        .into_iter()
+        .find_map(window_size);
+    if pty_fd >= 0 && let Some(ws) = ws {
+        unsafe { libc::ioctl(pty_fd, libc::TIOCSWINSZ, &ws) };
+    }
+    unsafe { *errno_location() = errno };
+}
+
+#[cfg(target_os = "linux")]
+unsafe fn errno_location() -> *mut libc::c_int {
+    unsafe { libc::__errno_location() }
+}
+
+#[cfg(any(target_os = "android", target_os = "netbsd", target_os = "openbsd"))]
+unsafe fn errno_location() -> *mut libc::c_int {
+    unsafe { libc::__errno() }
+}
+
+#[cfg(any(target_os = "macos", target_os = "ios", target_os = "freebsd", target_os = "dragonfly"))]
+unsafe fn errno_location() -> *mut libc::c_int {
+    unsafe { libc::__error() }
+}
+
+// 上次调用之后终端尺寸是否变化过
+pub fn take_resized() -> bool {
+    RESIZED.swap(false, Ordering::Relaxed)
+}
+
+// 根据环境变量粗略判断 fd 所在的终端是否支持 OSC 8 超链接
+pub fn supports_hyperlinks(fd: RawFd) -> bool {
+    if unsafe { libc::isatty(fd) } != 1 {
+        return false;
+    }
+    let env = |name: &str| std::env::var(name).unwrap_or_default();
+
+    if matches!(env("TERM_PROGRAM").as_str(), "iTerm.app" | "WezTerm" | "vscode" | "ghostty" | "Hyper") {
+        return true;
+    }
+    if !env("WT_SESSION").is_empty() || !env("KITTY_WINDOW_ID").is_empty() || !env("KONSOLE_VERSION").is_empty() {
+        return true;
+    }
+    // GNOME Terminal 等基于 VTE 的终端从 0.50 开始支持
+    if env("VTE_VERSION").parse::<u32>().is_ok_and(|v| v >= 5000) {
+        return true;
+    }
+    let term = env("TERM");
+    ["kitty", "foot", "alacritty", "wezterm", "ghostty"]
+        .iter()
+        .any(|name| term.contains(name))
+}
+
+// 根据 locale 判断终端能否显示 ☐ ☑ 等非 ASCII 符号
+pub fn supports_unicode() -> bool {
+    // 按 POSIX 优先级取第一个非空的 locale 变量
+    let locale = ["LC_ALL", "LC_CTYPE", "LANG"]
+        .iter()
+        .filter_map(|name| std::env::var(name).ok())
+        .find(|value| !value.is_empty());
+    match locale {
+        Some(locale) => {
+            let locale = locale.to_lowercase();
+            locale.contains("utf-8") || locale.contains("utf8")
+        }
+        // 未设置 locale 时大多数现代终端仍是 UTF-8
+        None => true,
+    }
+}
+
+// 终端背景是否是浅色：先用 OSC 11 查询背景色，终端不响应时根据 COLORFGBG 判断，都无法确定时返回 None
+// 输出不是终端时（如管道到 less）不发送查询，否则读取输出的程序可能把终端的响应当作按键
+pub fn light_background(query_terminal: bool) -> Option<bool> {
+    query_terminal.then(query_background).flatten().or_else(colorfgbg_background)
+}
+
+// 向 /dev/tty 发送 OSC 11 查询，紧接着发送几乎所有终端都会响应的 DA1 查询：
+// 先收到 DA1 的响应说明终端不支持 OSC 11，不必等到超时
+fn query_background() -> Option<bool> {
+    let mut tty = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .custom_flags(libc::O_NOCTTY)
+        .open("/dev/tty")
+        .ok()?;
+    let fd = tty.as_raw_fd();
+
+    // 关闭行缓冲和回显，响应才能立即读到且不会显示在屏幕上
+    let mut original: libc::termios = unsafe { std::mem::zeroed() };
+    if unsafe { libc::tcgetattr(fd, &mut original) } != 0 {
+        return None;
+    }
+    let mut raw = original;
+    raw.c_lflag &= !(libc::ICANON | libc::ECHO);
+    raw.c_cc[libc::VMIN] = 0;
+    raw.c_cc[libc::VTIME] = 0;
+    unsafe { libc::tcsetattr(fd, libc::TCSANOW, &raw) };
+
+    let mut response = Vec::new();
+    if tty.write_all(b"\x1b]11;?\x1b\\\x1b[c").and_then(|_| tty.flush()).is_ok() {
+        let deadline = std::time::Instant::now() + std::time::Duration::from_millis(200);
+        let mut buf = [0u8; 256];
+        while !has_device_attributes(&response) {
+            let remaining = deadline.saturating_duration_since(std::time::Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            let mut pollfd = libc::pollfd { fd, events: libc::POLLIN, revents: 0 };
+            if unsafe { libc::poll(&mut pollfd, 1, remaining.as_millis() as libc::c_int) } <= 0 {
+                break;
+            }
+            match tty.read(&mut buf) {
+                Ok(n) if n > 0 => response.extend_from_slice(&buf[..n]),
+                _ => break,
+            }
+        }
+    }
+    // 超时后才到达的响应丢弃，避免退出后出现在 shell 提示符中
+    unsafe {
+        libc::tcflush(fd, libc::TCIFLUSH);
+        libc::tcsetattr(fd, libc::TCSANOW, &original);
+    }
+
+    let (r, g, b) = parse_osc11(&String::from_utf8_lossy(&response))?;
+    Some(is_light(r, g, b))
+}
+
+// DA1 响应：ESC [ ? ... c
+fn has_device_attributes(response: &[u8]) -> bool {
+    let text = String::from_utf8_lossy(response);
+    text.find("\x1b[?").is_some_and(|start| text[start..].contains('c'))
+}
+
+// 解析 ESC ] 11 ; rgb:RRRR/GGGG/BBBB，每个分量 1-4 位十六进制，归一化到 0-1
+fn parse_osc11(response: &str) -> Option<(f64, f64, f64)> {
+    let start = response.find("\x1b]11;rgb:")? + "\x1b]11;rgb:".len();
+    let rest = &response[start..];
+    let end = rest.find(['\x07', '\x1b']).unwrap_or(rest.len());
+    let mut channels = rest[..end].split('/').map(|hex| {
+        let value = u32::from_str_radix(hex, 16).ok()?;
+        let max = (1u32 << (4 * hex.len().clamp(1, 4))) - 1;
+        Some(value as f64 / max as f64)
+    });
+    Some((channels.next()??, channels.next()??, channels.next()??))
+}
+
+// 相对亮度超过一半视为浅色背景
+fn is_light(r: f64, g: f64, b: f64) -> bool {
+    0.2126 * r + 0.7152 * g + 0.0722 * b > 0.5
+}
+
+// rxvt 等终端设置的 COLORFGBG="前景;背景"（可能是 "前景;default;背景"），背景是 ANSI 颜色编号
+fn colorfgbg_background() -> Option<bool> {
+    let value = std::env::var("COLORFGBG").ok()?;
+    let background: u8 = value.rsplit(';').next()?.parse().ok()?;
+    // 7（白色）和 9-15 中除 8（深灰）以外的亮色是浅色背景
+    Some(background == 7 || (9..=15).contains(&background))
+}
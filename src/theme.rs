@@ -0,0 +1,311 @@
+// 配色主题：每种 Markdown 元素的 SGR 样式
+// 内置深色、浅色两套预设，默认按终端背景色自动选择；
+// TOML 主题文件中没有设置的元素沿用 base 指定的预设（未指定时同样按终端背景选择）
+//
+//   base = "light"
+//   heading1 = "bold underline blue"
+//   inline_code = "#d75f00 on 254"
+//
+// 样式由空格分隔的词组成：bold、dim、italic、underline、strikethrough、reverse，
+// 颜色名（red、bright-red、gray 等）、0-255 的 256 色编号或 #rrggbb，on 之后的颜色是背景色
+use std::fs;
+
+#[derive(Clone)]
+pub struct Theme {
+    pub headings: [String; 6],
+    pub strong: String,
+    pub emphasis: String,
+    pub strikethrough: String,
+    pub highlight: String,
+    pub inline_code: String,
+    pub code: String,         // 没有语法高亮的代码
+    pub code_chrome: String,  // 代码块开始/结束行
+    pub code_border: String,  // 带边框代码块的边框和行号
+    pub quote: String,        // 引用竖线
+    pub list_marker: String,
+    pub rule: String,         // 分隔线
+    pub link: String,
+    pub table_header: String,
+    pub table_border: String,
+    pub alert_note: String, // 提示块的竖线和标题
+    pub alert_tip: String,
+    pub alert_important: String,
+    pub alert_warning: String,
+    pub alert_caution: String,
+    pub syntax: SyntaxTheme,
+}
+
+// 代码块语法高亮中各类词法单元的样式
+#[derive(Clone)]
+pub struct SyntaxTheme {
+    pub keyword: String,
+    pub type_name: String,
+    pub literal: String, // true / false / null 等
+    pub number: String,
+    pub string: String,
+    pub comment: String,
+    pub function: String,  // 函数调用、宏
+    pub attribute: String, // 属性、装饰器、预处理指令
+    pub key: String,       // JSON / YAML / TOML 的键
+    pub variable: String,  // shell 变量、Rust 生命周期
+}
+
+impl Theme {
+    // 深色背景：行内代码用深灰底，避免浅色块过于刺眼
+    pub fn dark() -> Self {
+        Self {
+            headings: [
+                "\x1b[1;33m".to_string(),
+                "\x1b[1;33m".to_string(),
+                "\x1b[1;36m".to_string(),
+                "\x1b[1;36m".to_string(),
+                "\x1b[36m".to_string(),
+                "\x1b[36m".to_string(),
+            ],
+            strong: "\x1b[1;33m".to_string(),
+            emphasis: "\x1b[3m".to_string(),
+            strikethrough: "\x1b[9m".to_string(),
+            highlight: "\x1b[30;103m".to_string(),
+            inline_code: "\x1b[48;5;236m\x1b[38;5;215m".to_string(),
+            code: "\x1b[32m".to_string(),
+            code_chrome: "\x1b[1;33m".to_string(),
+            code_border: "\x1b[90m".to_string(),
+            quote: "\x1b[1;33m".to_string(),
+            list_marker: "\x1b[1;35m".to_string(),
+            rule: "\x1b[90m".to_string(),
+            link: "\x1b[34m\x1b[4m".to_string(),
+            table_header: "\x1b[1;33m".to_string(),
+            table_border: "\x1b[90m".to_string(),
+            alert_note: "\x1b[34m".to_string(),
+            alert_tip: "\x1b[32m".to_string(),
+            alert_important: "\x1b[35m".to_string(),
+            alert_warning: "\x1b[33m".to_string(),
+            alert_caution: "\x1b[31m".to_string(),
+            syntax: SyntaxTheme {
+                keyword: "\x1b[35m".to_string(),
+                type_name: "\x1b[36m".to_string(),
+                literal: "\x1b[33m".to_string(),
+                number: "\x1b[33m".to_string(),
+                string: "\x1b[32m".to_string(),
+                comment: "\x1b[90m\x1b[3m".to_string(),
+                function: "\x1b[34m".to_string(),
+                attribute: "\x1b[38;5;130m".to_string(),
+                key: "\x1b[34m".to_string(),
+                variable: "\x1b[36m".to_string(),
+            },
+        }
+    }
+
+    // 浅色背景：黄色文字对比度太低，改用深一些的颜色
+    pub fn light() -> Self {
+        Self {
+            headings: [
+                "\x1b[1;34m".to_string(),
+                "\x1b[1;34m".to_string(),
+                "\x1b[1;36m".to_string(),
+                "\x1b[1;36m".to_string(),
+                "\x1b[36m".to_string(),
+                "\x1b[36m".to_string(),
+            ],
+            strong: "\x1b[1;38;5;130m".to_string(),
+            emphasis: "\x1b[3m".to_string(),
+            strikethrough: "\x1b[9m".to_string(),
+            highlight: "\x1b[30;103m".to_string(),
+            inline_code: "\x1b[48;5;253m\x1b[38;5;130m".to_string(),
+            code: "\x1b[32m".to_string(),
+            code_chrome: "\x1b[1;32m".to_string(),
+            code_border: "\x1b[90m".to_string(),
+            quote: "\x1b[1;38;5;130m".to_string(),
+            list_marker: "\x1b[1;35m".to_string(),
+            rule: "\x1b[90m".to_string(),
+            link: "\x1b[34m\x1b[4m".to_string(),
+            table_header: "\x1b[1;34m".to_string(),
+            table_border: "\x1b[90m".to_string(),
+            alert_note: "\x1b[34m".to_string(),
+            alert_tip: "\x1b[32m".to_string(),
+            alert_important: "\x1b[35m".to_string(),
+            alert_warning: "\x1b[38;5;166m".to_string(),
+            alert_caution: "\x1b[31m".to_string(),
+            syntax: SyntaxTheme {
+                keyword: "\x1b[35m".to_string(),
+                type_name: "\x1b[36m".to_string(),
+                literal: "\x1b[38;5;166m".to_string(),
+                number: "\x1b[38;5;166m".to_string(),
+                string: "\x1b[32m".to_string(),
+                comment: "\x1b[90m\x1b[3m".to_string(),
+                function: "\x1b[34m".to_string(),
+                attribute: "\x1b[38;5;130m".to_string(),
+                key: "\x1b[34m".to_string(),
+                variable: "\x1b[36m".to_string(),
+            },
+        }
+    }
+
+    // 预设名称（auto、dark、light），或 TOML 主题文件的路径；light_background 是检测到的终端背景
+    pub fn load(name: &str, light_background: bool) -> Result<Self, String> {
+        let detected = if light_background { Self::light() } else { Self::dark() };
+        match name {
+            "auto" => Ok(detected),
+            "dark" => Ok(Self::dark()),
+            "light" => Ok(Self::light()),
+            path => {
+                let text = fs::read_to_string(path).map_err(|e| format!("{}: {}", path, e))?;
+                Self::parse(&text, detected).map_err(|e| format!("{}: {}", path, e))
+            }
+        }
+    }
+
+    fn parse(text: &str, detected: Self) -> Result<Self, String> {
+        let table: toml::Table = text.parse().map_err(|e: toml::de::Error| e.message().to_string())?;
+        let mut theme = match table.get("base").map(|v| v.as_str()) {
+            None | Some(Some("auto")) => detected,
+            Some(Some("dark")) => Self::dark(),
+            Some(Some("light")) => Self::light(),
+            Some(_) => return Err("base must be \"auto\", \"dark\" or \"light\"".to_string()),
+        };
+
+        for (key, value) in &table {
+            if key == "base" {
+                continue;
+            }
+            let spec = value.as_str().ok_or_else(|| format!("{}: style must be a string", key))?;
+            let style = parse_style(spec).map_err(|e| format!("{}: {}", key, e))?;
+            let slot = match key.as_str() {
+                "heading1" => &mut theme.headings[0],
+                "heading2" => &mut theme.headings[1],
+                "heading3" => &mut theme.headings[2],
+                "heading4" => &mut theme.headings[3],
+                "heading5" => &mut theme.headings[4],
+                "heading6" => &mut theme.headings[5],
+                "strong" => &mut theme.strong,
+                "emphasis" => &mut theme.emphasis,
+                "strikethrough" => &mut theme.strikethrough,
+                "highlight" => &mut theme.highlight,
+                "inline_code" => &mut theme.inline_code,
+                "code" => &mut theme.code,
+                "code_chrome" => &mut theme.code_chrome,
+                "code_border" => &mut theme.code_border,
+                "quote" => &mut theme.quote,
+                "list_marker" => &mut theme.list_marker,
+                "rule" => &mut theme.rule,
+                "link" => &mut theme.link,
+                "table_header" => &mut theme.table_header,
+                "table_border" => &mut theme.table_border,
+                "alert_note" => &mut theme.alert_note,
+                "alert_tip" => &mut theme.alert_tip,
+                "alert_important" => &mut theme.alert_important,
+                "alert_warning" => &mut theme.alert_warning,
+                "alert_caution" => &mut theme.alert_caution,
+                "syntax_keyword" => &mut theme.syntax.keyword,
+                "syntax_type" => &mut theme.syntax.type_name,
+                "syntax_literal" => &mut theme.syntax.literal,
+                "syntax_number" => &mut theme.syntax.number,
+                "syntax_string" => &mut theme.syntax.string,
+                "syntax_comment" => &mut theme.syntax.comment,
+                "syntax_function" => &mut theme.syntax.function,
+                "syntax_attribute" => &mut theme.syntax.attribute,
+                "syntax_key" => &mut theme.syntax.key,
+                "syntax_variable" => &mut theme.syntax.variable,
+                _ => return Err(format!("unknown element \"{}\"", key)),
+            };
+            *slot = style;
+        }
+        Ok(theme)
+    }
+}
+
+// 把 "bold yellow on 236" 这样的样式描述转换为 SGR 转义序列
+fn parse_style(spec: &str) -> Result<String, String> {
+    let mut params = Vec::new();
+    let mut background = false;
+    for word in spec.split_whitespace() {
+        let word = word.to_ascii_lowercase();
+        let attribute = match word.as_str() {
+            "bold" => Some("1"),
+            "dim" => Some("2"),
+            "italic" => Some("3"),
+            "underline" => Some("4"),
+            "reverse" => Some("7"),
+            "strikethrough" => Some("9"),
+            _ => None,
+        };
+        if let Some(attribute) = attribute {
+            params.push(attribute.to_string());
+        } else if word == "on" {
+            background = true;
+        } else {
+            params.push(parse_color(&word, background).ok_or_else(|| format!("invalid style \"{}\"", word))?);
+        }
+    }
+    if params.is_empty() {
+        return Ok(String::new());
+    }
+    Ok(format!("\x1b[{}m", params.join(";")))
+}
+
+// 颜色名、256 色编号或 #rrggbb
+fn parse_color(word: &str, background: bool) -> Option<String> {
+    const NAMES: [&str; 8] = ["black", "red", "green", "yellow", "blue", "magenta", "cyan", "white"];
+    let (base, extended) = if background { (40, 48) } else { (30, 38) };
+
+    if let Some(hex) = word.strip_prefix('#') {
+        if hex.len() != 6 || !hex.chars().all(|c| c.is_ascii_hexdigit()) {
+            return None;
+        }
+        let channel = |i: usize| u8::from_str_radix(&hex[i..i + 2], 16).ok();
+        return Some(format!("{};2;{};{};{}", extended, channel(0)?, channel(2)?, channel(4)?));
+    }
+    if let Ok(index) = word.parse::<u8>() {
+        return Some(format!("{};5;{}", extended, index));
+    }
+    if word == "gray" || word == "grey" {
+        return Some((base + 60).to_string());
+    }
+    let (name, bright) = match word.strip_prefix("bright-") {
+        Some(name) => (name, 60),
+        None => (word, 0),
+    };
+    let index = NAMES.iter().position(|&n| n == name)?;
+    Some((base + bright + index).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn style_words() {
+        assert_eq!(parse_style("bold underline blue").unwrap(), "\x1b[1;4;34m");
+        assert_eq!(parse_style("bright-red on black").unwrap(), "\x1b[91;40m");
+        assert_eq!(parse_style("Gray on grey").unwrap(), "\x1b[90;100m");
+        assert_eq!(parse_style("215 on 236").unwrap(), "\x1b[38;5;215;48;5;236m");
+        assert_eq!(parse_style("#d75f00 on #FFFFFF").unwrap(), "\x1b[38;2;215;95;0;48;2;255;255;255m");
+        assert_eq!(parse_style("").unwrap(), "");
+    }
+
+    #[test]
+    fn invalid_styles() {
+        assert!(parse_style("blinking").is_err());
+        assert!(parse_style("256").is_err());
+        assert!(parse_style("#12345").is_err());
+        assert!(parse_style("#12345g").is_err());
+        // 非 ASCII 字符不能按字节切分
+        assert!(parse_style("#aé€").is_err());
+    }
+
+    #[test]
+    fn theme_files() {
+        let theme = Theme::parse("base = \"light\"\nstrong = \"red\"\nsyntax_number = \"bold\"", Theme::dark()).unwrap();
+        assert_eq!(theme.strong, "\x1b[31m");
+        assert_eq!(theme.syntax.number, "\x1b[1m");
+        assert_eq!(theme.headings[0], Theme::light().headings[0]);
+
+        // 没有 base 时沿用检测到的预设
+        let theme = Theme::parse("", Theme::light()).unwrap();
+        assert_eq!(theme.inline_code, Theme::light().inline_code);
+
+        assert!(Theme::parse("base = \"blue\"", Theme::dark()).is_err());
+        assert!(Theme::parse("headline = \"red\"", Theme::dark()).is_err());
+        assert!(Theme::parse("strong = 1", Theme::dark()).is_err());
+    }
+}
@@ -0,0 +1,92 @@
+// 终端显示宽度计算
+// 跳过 ANSI 转义序列（CSI / OSC），CJK 和 emoji 等宽字符按 2 列计算
+use crate::RESET;
+use unicode_width::UnicodeWidthChar;
+
+// 返回 text 开头的转义序列字节长度，不是转义序列时返回 0
+pub fn escape_len(text: &str) -> usize {
+    let bytes = text.as_bytes();
+    if bytes.first() != Some(&0x1b) {
+        return 0;
+    }
+    match bytes.get(1) {
+        // CSI: ESC [ 参数... 结束字节(0x40-0x7E)
+        Some(b'[') => {
+            for (i, &b) in bytes.iter().enumerate().skip(2) {
+                if (0x40..=0x7e).contains(&b) {
+                    return i + 1;
+                }
+            }
+            bytes.len()
+        }
+        // OSC: ESC ] ... 以 BEL 或 ESC \ 结束（OSC 8 超链接等）
+        Some(b']') => {
+            let mut i = 2;
+            while i < bytes.len() {
+                if bytes[i] == 0x07 {
+                    return i + 1;
+                }
+                if bytes[i] == 0x1b && bytes.get(i + 1) == Some(&b'\\') {
+                    return i + 2;
+                }
+                i += 1;
+            }
+            bytes.len()
+        }
+        Some(_) => {
+            // ESC 加单个字符
+            1 + text[1..].chars().next().map_or(0, |c| c.len_utf8())
+        }
+        None => 1,
+    }
+}
+
+// 计算字符串在终端中的可见宽度
+pub fn display_width(text: &str) -> usize {
+    let mut width = 0;
+    let mut pos = 0;
+    while pos < text.len() {
+        let skip = escape_len(&text[pos..]);
+        if skip > 0 {
+            pos += skip;
+            continue;
+        }
+        let c = text[pos..].chars().next().unwrap();
+        width += c.width().unwrap_or(0);
+        pos += c.len_utf8();
+    }
+    width
+}
+
+// 按显示宽度切分带转义序列的文本，返回 (不超过 width 列的前半部分, 剩余部分)
+// 切分处仍然生效的 SGR 样式在前半部分结尾重置，并在剩余部分开头重新应用
+pub fn split_at_width(text: &str, width: usize) -> (String, String) {
+    let mut used = 0;
+    let mut pos = 0;
+    let mut active = String::new();
+    while pos < text.len() {
+        let skip = escape_len(&text[pos..]);
+        if skip > 0 {
+            let sequence = &text[pos..pos + skip];
+            if sequence == RESET || sequence == "\x1b[m" {
+                active.clear();
+            } else if sequence.starts_with("\x1b[") && sequence.ends_with('m') {
+                active.push_str(sequence);
+            }
+            pos += skip;
+            continue;
+        }
+        let c = text[pos..].chars().next().unwrap();
+        let w = c.width().unwrap_or(0);
+        if used + w > width {
+            break;
+        }
+        used += w;
+        pos += c.len_utf8();
+    }
+    if pos >= text.len() {
+        return (text.to_string(), String::new());
+    }
+    let reset = if active.is_empty() { "" } else { RESET };
+    (format!("{}{}", &text[..pos], reset), format!("{}{}", active, &text[pos..]))
+}
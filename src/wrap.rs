@@ -0,0 +1,228 @@
+// 按显示宽度折行：在空白处或宽字符（CJK、emoji）之间断行，续行加上悬挂缩进
+// 折行处仍然生效的样式和超链接在行尾关闭，续行缩进之后重新打开
+use crate::width::{display_width, escape_len};
+use crate::RESET;
+use unicode_width::UnicodeWidthChar;
+
+const LINK_END: &str = "\x1b]8;;\x1b\\";
+
+// 不能出现在行首的标点，跟在前一个单位后面
+const NO_BREAK_BEFORE: &str = "，。、；：！？）」』】》〉”’…,.;:!?)]}";
+
+// 折行单位：一个单词、一个宽字符或一段空格；前面的转义序列附在单位开头
+struct Unit {
+    text: String,
+    width: usize,
+    space: bool,
+    wide: bool,
+}
+
+fn split_units(line: &str) -> Vec<Unit> {
+    let mut units: Vec<Unit> = Vec::new();
+    let mut escapes = String::new();
+    let mut pos = 0;
+    while pos < line.len() {
+        let skip = escape_len(&line[pos..]);
+        if skip > 0 {
+            escapes.push_str(&line[pos..pos + skip]);
+            pos += skip;
+            continue;
+        }
+        let c = line[pos..].chars().next().unwrap();
+        pos += c.len_utf8();
+        let width = c.width().unwrap_or(0);
+        let space = c == ' ';
+        let wide = width == 2;
+
+        let joins = match units.last() {
+            None => false,
+            Some(last) if NO_BREAK_BEFORE.contains(c) => !last.space,
+            Some(last) if space => last.space,
+            // 窄字符组成单词，宽字符各自成为一个单位
+            Some(last) => !last.space && !last.wide && !wide,
+        };
+        if joins {
+            let last = units.last_mut().unwrap();
+            last.text.push_str(&std::mem::take(&mut escapes));
+            last.text.push(c);
+            last.width += width;
+        } else {
+            units.push(Unit { text: format!("{}{}", std::mem::take(&mut escapes), c), width, space, wide });
+        }
+    }
+    // 行尾的转义序列（通常是 RESET）
+    if !escapes.is_empty() {
+        units.push(Unit { text: escapes, width: 0, space: false, wide: false });
+    }
+    units
+}
+
+// 输出状态：当前列数和折行时需要恢复的样式、超链接
+struct Writer<'a> {
+    out: String,
+    column: usize,
+    width: usize,
+    indent: &'a str,
+    active: String,
+    link: Option<String>,
+    line_empty: bool,
+}
+
+impl Writer<'_> {
+    // 逐字符输出，超过宽度时在字符之间断行（过长的单词）
+    fn emit(&mut self, text: &str, visible: bool) {
+        let mut pos = 0;
+        while pos < text.len() {
+            let skip = escape_len(&text[pos..]);
+            if skip > 0 {
+                let sequence = &text[pos..pos + skip];
+                self.track(sequence);
+                self.out.push_str(sequence);
+                pos += skip;
+                continue;
+            }
+            let c = text[pos..].chars().next().unwrap();
+            pos += c.len_utf8();
+            if !visible {
+                continue;
+            }
+            let width = c.width().unwrap_or(0);
+            if !self.line_empty && self.column + width > self.width {
+                self.break_line();
+            }
+            self.out.push(c);
+            self.column += width;
+            self.line_empty = false;
+        }
+    }
+
+    fn track(&mut self, sequence: &str) {
+        if sequence == RESET || sequence == "\x1b[m" {
+            self.active.clear();
+        } else if sequence.starts_with("\x1b[") && sequence.ends_with('m') {
+            self.active.push_str(sequence);
+        } else if sequence == LINK_END {
+            self.link = None;
+        } else if sequence.starts_with("\x1b]8;") {
+            self.link = Some(sequence.to_string());
+        }
+    }
+
+    fn break_line(&mut self) {
+        if self.link.is_some() {
+            self.out.push_str(LINK_END);
+        }
+        if !self.active.is_empty() {
+            self.out.push_str(RESET);
+        }
+        self.out.push('\n');
+        self.out.push_str(self.indent);
+        self.out.push_str(&self.active);
+        if let Some(link) = &self.link {
+            self.out.push_str(link);
+        }
+        self.column = display_width(self.indent);
+        self.line_empty = true;
+    }
+}
+
+// 把渲染好的文本按 width 列折行，续行以 indent 开头；每个换行符分隔的行单独处理
+pub fn wrap(text: &str, width: usize, indent: &str) -> String {
+    // 宽度不足以容纳缩进和内容时不折行
+    if width <= display_width(indent) + 1 {
+        return text.to_string();
+    }
+    text.split_inclusive('\n').map(|line| wrap_line(line, width, indent)).collect()
+}
+
+fn wrap_line(line: &str, width: usize, indent: &str) -> String {
+    let body = line.trim_end_matches(['\r', '\n']);
+    let ending = &line[body.len()..];
+    if display_width(body) <= width {
+        return line.to_string();
+    }
+
+    let mut writer = Writer {
+        out: String::new(),
+        column: 0,
+        width,
+        indent,
+        active: String::new(),
+        link: None,
+        line_empty: true,
+    };
+    let mut spaces: Vec<Unit> = Vec::new();
+    for unit in split_units(body) {
+        if unit.space {
+            spaces.push(unit);
+            continue;
+        }
+        let space_width: usize = spaces.iter().map(|s| s.width).sum();
+        let overflows = writer.column + space_width + unit.width > width;
+        // 断行处的空格不输出，只保留其中的转义序列
+        let break_here = overflows && !writer.line_empty && unit.width > 0;
+        for space in spaces.drain(..) {
+            writer.emit(&space.text, !break_here);
+        }
+        if break_here {
+            writer.break_line();
+        }
+        writer.emit(&unit.text, true);
+    }
+    for space in spaces {
+        writer.emit(&space.text, false);
+    }
+    writer.out.push_str(ending);
+    writer.out
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn short_lines_unchanged() {
+        assert_eq!(wrap("hello world\n", 20, ""), "hello world\n");
+        assert_eq!(wrap("hello world", 11, ""), "hello world");
+    }
+
+    #[test]
+    fn breaks_at_spaces() {
+        assert_eq!(wrap("aaa bbb ccc\n", 7, ""), "aaa bbb\nccc\n");
+        // 断行处的空格不输出
+        assert_eq!(wrap("aaa   bbb", 5, ""), "aaa\nbbb");
+    }
+
+    #[test]
+    fn hanging_indent() {
+        assert_eq!(wrap("- aaa bbb ccc\n", 8, "  "), "- aaa\n  bbb\n  ccc\n");
+        assert_eq!(wrap("1. 中文中文中文\n", 9, "   "), "1. 中文中\n   文中文\n");
+    }
+
+    #[test]
+    fn wide_characters() {
+        assert_eq!(wrap("中文中文中文", 6, ""), "中文中\n文中文");
+        // 行首禁则：逗号跟在前一个字后面
+        assert_eq!(wrap("中文中，文", 6, ""), "中文\n中，文");
+    }
+
+    #[test]
+    fn long_words_hard_broken() {
+        assert_eq!(wrap("abcdefghij", 4, ""), "abcd\nefgh\nij");
+    }
+
+    #[test]
+    fn styles_reopened_after_break() {
+        assert_eq!(wrap("\x1b[1mbold text\x1b[0m", 5, ""), "\x1b[1mbold\x1b[0m\n\x1b[1mtext\x1b[0m");
+        let link = "\x1b]8;;https://a.b\x1b\\";
+        assert_eq!(
+            wrap(&format!("{}one two{}", link, LINK_END), 4, ""),
+            format!("{}one{}\n{}two{}", link, LINK_END, link, LINK_END)
+        );
+    }
+
+    #[test]
+    fn too_narrow_to_wrap() {
+        assert_eq!(wrap("aaa bbb", 2, "  "), "aaa bbb");
+    }
+}